@@ -1,4 +1,4 @@
-use std::{error, fs::File, io::{self, BufRead}};
+use std::{error, fmt, fs::File, io::{self, BufRead}, num::{IntErrorKind, ParseIntError}};
 
 // This lets us bubble up all errors to main() regardless of type
 type Error = Box<dyn error::Error>;
@@ -7,6 +7,92 @@ pub type Result<T> = std::result::Result<T, Error>;
 // const INPUT: &str = "data/day2_1_example.txt";
 const INPUT: &str = "data/day2_1.txt";
 
+/// Number of levels the Problem Dampener may remove from a report.
+const MAX_REMOVALS: usize = 1;
+
+/// Which invariant a pair of adjacent levels broke.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rule {
+    /// Adjacent levels differ by more than three.
+    StepTooLarge,
+    /// Adjacent levels are equal.
+    StepZero,
+    /// The levels switched between increasing and decreasing.
+    DirectionChange,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::StepTooLarge => write!(f, "step too large"),
+            Rule::StepZero => write!(f, "step of zero"),
+            Rule::DirectionChange => write!(f, "direction change"),
+        }
+    }
+}
+
+/// The first place a report breaks one of the invariants.
+///
+/// `index` (0-based) is the level that could not follow the one before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Violation {
+    index: usize,
+    rule: Rule,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        /* Levels are shown 1-based, like reports */
+        write!(f, "{} at level {}", self.rule, self.index + 1)
+    }
+}
+
+/// Outcome of validating a single report.
+#[derive(Debug, Clone, PartialEq)]
+enum Validation {
+    /// The report is valid as-is.
+    Safe,
+    /// The report is valid once the levels at these indices are removed.
+    Dampened { removed: Vec<usize> },
+    /// The report is not valid, even with the dampener.
+    Unsafe(Violation),
+}
+
+impl Validation {
+    fn is_safe(&self) -> bool {
+        !matches!(self, Validation::Unsafe(_))
+    }
+}
+
+/// Why a level in the input could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LevelError {
+    NotANumber,
+    Negative,
+    OutOfRange,
+}
+
+/// A level that could not be parsed, with the line (1-based) it came from.
+#[derive(Debug, Clone, PartialEq)]
+struct ParseError {
+    line: usize,
+    token: String,
+    kind: LevelError,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self.kind {
+            LevelError::NotANumber => "is not a number",
+            LevelError::Negative => "is negative",
+            LevelError::OutOfRange => "is out of range",
+        };
+        write!(f, "line {}: level `{}` {}", self.line, self.token, reason)
+    }
+}
+
+impl error::Error for ParseError {}
+
 fn main() -> Result<()> {
     let cwd = std::env::current_dir()?;
     // println!("The current directory is {}", cwd.display());
@@ -17,16 +103,52 @@ fn main() -> Result<()> {
     /* Parse input file */
     let input = parse_input(&in_file)?;
 
+    /* Removal budget can be overridden with `--dampen <k>` */
+    let mut max_removals = MAX_REMOVALS;
+    let mut explain = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dampen" => {
+                max_removals = args.next().ok_or("--dampen requires a value")?.parse()?;
+            }
+            "--explain" => explain = true,
+            _ => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
     /* Count valid reports */
     let mut valid_cnt = 0;
+    let mut dampened_cnt = 0;
 
-    for report in input {
-        if is_report_valid2(&report) {
+    for (line, report) in input.iter().enumerate() {
+        let validation = validate_report(report);
+        if validation.is_safe() {
             valid_cnt += 1;
         }
+
+        let dampened = validate_report_dampened(report, max_removals);
+        if dampened.is_safe() {
+            dampened_cnt += 1;
+        }
+
+        if explain {
+            if let Validation::Unsafe(violation) = validation {
+                print!("Report {} {:?}: {}", line + 1, report, violation);
+                match dampened {
+                    Validation::Dampened { removed } => {
+                        let levels: Vec<usize> = removed.iter().map(|i| i + 1).collect();
+                        println!(", fixed by removing levels {:?}", levels);
+                    }
+                    _ => println!(", not fixable with {} removals", max_removals),
+                }
+            }
+        }
     }
 
-    println!("Valid reports: {}", valid_cnt);
+    /* `Valid reports` stays the dampened count, as it always was */
+    println!("Valid reports (undampened): {}", valid_cnt);
+    println!("Valid reports: {}", dampened_cnt);
 
     Ok(())
 }
@@ -39,29 +161,34 @@ fn main() -> Result<()> {
 ///     2. Any two adjacent levels differ by at least one
 ///         and at most three.
 ///
-fn is_report_valid( report: &[u32]) -> bool {
+/// Returns the first violation found, checking invariant 2 before invariant 1
+/// at each step. Reports with fewer than two levels have no adjacent pair to
+/// break either invariant, so they are always safe.
+///
+fn validate_report( report: &[u32]) -> Validation {
+    if report.len() < 2 {
+        return Validation::Safe;
+    }
+
     let is_increasing = report[0] < report[1];
 
     for i in 0..(report.len()-1) {
         /* Check invariant 2 */
         let diff = report[i].abs_diff(report[i+1]);
-        if diff > 3 || diff < 1 {
-            return false
+        if diff == 0 {
+            return Validation::Unsafe(Violation { index: i + 1, rule: Rule::StepZero });
+        }
+        if diff > 3 {
+            return Validation::Unsafe(Violation { index: i + 1, rule: Rule::StepTooLarge });
         }
 
         /* Check invariant 1. */
-        if is_increasing {
-            if report[i] > report[i+1] {
-                return false
-            }
-        } else {
-            if report[i] < report[i+1] {
-                return false
-            }
+        if is_increasing != (report[i] < report[i+1]) {
+            return Validation::Unsafe(Violation { index: i + 1, rule: Rule::DirectionChange });
         }
     }
 
-    true
+    Validation::Safe
 }
 
 /// Check the validity of a report.
@@ -74,12 +201,15 @@ fn is_report_valid( report: &[u32]) -> bool {
 ///     3. If removing one level makes the report valid,
 ///         consider the report valid.
 ///
+/// Brute force reference for `validate_report_dampened`.
+///
+#[cfg(test)]
 fn is_report_valid2( report: &[u32]) -> bool {
     let mut copy = report.to_vec();
 
     let mut i: usize = 0;
 
-    while !is_report_valid(&copy) {
+    while !validate_report(&copy).is_safe() {
         if i == report.len() {
             return false;
         }
@@ -95,17 +225,133 @@ fn is_report_valid2( report: &[u32]) -> bool {
     true
 }
 
+/// Check the validity of a report, allowing up to `max_removals` levels to
+/// be removed.
+///
+/// Same invariants as `is_report_valid2`, but instead of re-validating a copy
+/// of the report for every possible removal this runs a small dynamic program
+/// once per direction, costing O(n*k) with no allocation beyond the table.
+///
+/// When the report needs the dampener, the returned removal set is the
+/// smallest one found. When it cannot be fixed, the violation is the one
+/// `validate_report` reports for the unmodified report.
+///
+fn validate_report_dampened(report: &[u32], max_removals: usize) -> Validation {
+    let violation = match validate_report(report) {
+        Validation::Unsafe(violation) => violation,
+        safe => return safe,
+    };
+
+    let increasing = |a: u32, b: u32| a < b && b - a <= 3;
+    let decreasing = |a: u32, b: u32| a > b && a - b <= 3;
+
+    let removed = match (min_removals(report, max_removals, increasing), min_removals(report, max_removals, decreasing)) {
+        (Some(inc), Some(dec)) => if dec.len() < inc.len() { dec } else { inc },
+        (Some(inc), None) => inc,
+        (None, Some(dec)) => dec,
+        (None, None) => return Validation::Unsafe(violation),
+    };
+
+    Validation::Dampened { removed }
+}
+
+/// Find the fewest levels that must be removed from `report` so that every
+/// pair of adjacent remaining levels satisfies `step_ok`.
+///
+/// Returns the indices of the removed levels, or `None` if more than
+/// `max_removals` levels would have to go.
+///
+fn min_removals<F>(report: &[u32], max_removals: usize, step_ok: F) -> Option<Vec<usize>>
+where
+    F: Fn(u32, u32) -> bool,
+{
+    let n = report.len();
+    if n == 0 {
+        return Some(Vec::new());
+    }
+
+    /* best[i]: fewest removals among levels 0..=i such that level i is kept
+     * and the kept levels so far are valid. Only the last `max_removals + 1`
+     * kept levels can be the predecessor of i without blowing the budget.
+     * prev[i] remembers which predecessor gave that cost. */
+    let mut best: Vec<Option<usize>> = vec![None; n];
+    let mut prev: Vec<Option<usize>> = vec![None; n];
+    let mut answer: Option<(usize, usize)> = None; // (removals, last kept level)
+
+    for i in 0..n {
+        /* Keep level i as the first level, dropping everything before it */
+        let mut cost = if i <= max_removals { Some(i) } else { None };
+
+        let lo = i.saturating_sub(max_removals + 1);
+        for p in lo..i {
+            if let Some(prev_cost) = best[p] {
+                let c = prev_cost + (i - p - 1);
+                if c <= max_removals && step_ok(report[p], report[i]) && cost.is_none_or(|cur| c < cur) {
+                    cost = Some(c);
+                    prev[i] = Some(p);
+                }
+            }
+        }
+
+        best[i] = cost;
+
+        /* Level i as the last kept level, dropping everything after it */
+        if let Some(c) = cost {
+            let total = c + (n - 1 - i);
+            if total <= max_removals && answer.is_none_or(|(cur, _)| total < cur) {
+                answer = Some((total, i));
+            }
+        }
+    }
+
+    /* Walk the chain of kept levels back to recover what was removed */
+    let (_, last) = answer?;
+    let mut kept = vec![false; n];
+    let mut i = Some(last);
+    while let Some(k) = i {
+        kept[k] = true;
+        i = prev[k];
+    }
+
+    Some((0..n).filter(|&i| !kept[i]).collect())
+}
+
 
+/// Parse one report per line.
+///
+/// A blank line is an empty report, so report numbers always match line
+/// numbers. Levels must fit in a `u32`; anything else is reported as a
+/// `ParseError` naming the line and offending token.
+///
 fn parse_input(f: &File) -> Result<Vec<Vec<u32>>> {
-    let mut reports = Vec::new();
     let reader = io::BufReader::new(f);
+    let mut lines = Vec::new();
 
     for line in reader.lines() {
-        let line = line?;
+        lines.push(line?);
+    }
+
+    Ok(parse_reports(&lines)?)
+}
+
+fn parse_reports<S: AsRef<str>>(lines: &[S]) -> std::result::Result<Vec<Vec<u32>>, ParseError> {
+    let mut reports = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
         let mut levels = Vec::new();
 
-        for ch in line.split_whitespace() {
-            let lvl: u32 = ch.parse()?;
+        for ch in line.as_ref().split_whitespace() {
+            let lvl: u32 = ch.parse().map_err(|e: ParseIntError| {
+                let digits = ch.strip_prefix('-').unwrap_or_default();
+                let kind = if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+                    LevelError::Negative
+                } else if *e.kind() == IntErrorKind::PosOverflow {
+                    LevelError::OutOfRange
+                } else {
+                    LevelError::NotANumber
+                };
+                ParseError { line: i + 1, token: ch.to_string(), kind }
+            })?;
             levels.push(lvl);
         }
         reports.push(levels);
@@ -113,3 +359,117 @@ fn parse_input(f: &File) -> Result<Vec<Vec<u32>>> {
 
     Ok(reports)
 }
+
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn example_input() {
+        let reports: [&[u32]; 6] = [
+            &[7, 6, 4, 2, 1],
+            &[1, 2, 7, 8, 9],
+            &[9, 7, 6, 2, 1],
+            &[1, 3, 2, 4, 5],
+            &[8, 6, 4, 4, 1],
+            &[1, 3, 6, 7, 9],
+        ];
+        let expected = [true, false, false, true, true, true];
+
+        for (report, valid) in reports.iter().zip(expected) {
+            assert_eq!(valid, validate_report_dampened(report, 1).is_safe(), "{:?}", report);
+        }
+    }
+
+    #[test]
+    fn explain_example() {
+        assert_eq!(Validation::Safe, validate_report(&[7, 6, 4, 2, 1]));
+        assert_eq!("step too large at level 3", Violation { index: 2, rule: Rule::StepTooLarge }.to_string());
+        assert_eq!(
+            Validation::Unsafe(Violation { index: 2, rule: Rule::StepTooLarge }),
+            validate_report(&[1, 2, 7, 8, 9]),
+        );
+        assert_eq!(
+            Validation::Unsafe(Violation { index: 2, rule: Rule::DirectionChange }),
+            validate_report(&[1, 3, 2, 4, 5]),
+        );
+        assert_eq!(
+            Validation::Unsafe(Violation { index: 3, rule: Rule::StepZero }),
+            validate_report(&[8, 6, 4, 4, 1]),
+        );
+
+        /* Dropping either of the offending pair works. Ties keep the earliest
+         * predecessor, so the later level of the pair is the one removed. */
+        assert_eq!(
+            Validation::Dampened { removed: vec![2] },
+            validate_report_dampened(&[1, 3, 2, 4, 5], 1),
+        );
+        assert_eq!(
+            Validation::Dampened { removed: vec![3] },
+            validate_report_dampened(&[8, 6, 4, 4, 1], 1),
+        );
+        assert_eq!(
+            Validation::Unsafe(Violation { index: 2, rule: Rule::StepTooLarge }),
+            validate_report_dampened(&[1, 2, 7, 8, 9], 1),
+        );
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(2024);
+
+        for _ in 0..5000 {
+            let len = rng.gen_range(3..9);
+            let mut report = vec![rng.gen_range(10..20)];
+            for _ in 1..len {
+                let step: i32 = rng.gen_range(-4..6);
+                report.push((*report.last().unwrap() as i32 + step) as u32);
+            }
+
+            assert_eq!(is_report_valid2(&report), validate_report_dampened(&report, 1).is_safe(), "{:?}", report);
+            assert_eq!(validate_report(&report).is_safe(), validate_report_dampened(&report, 0).is_safe(), "{:?}", report);
+
+            /* Whatever the dampener removes must actually leave a valid report */
+            if let Validation::Dampened { removed } = validate_report_dampened(&report, 2) {
+                let rest: Vec<u32> = report.iter().enumerate()
+                    .filter(|(i, _)| !removed.contains(i))
+                    .map(|(_, l)| *l)
+                    .collect();
+                assert!(rest.len() < 2 || validate_report(&rest).is_safe(), "{:?} - {:?}", report, removed);
+            }
+        }
+    }
+
+    #[test]
+    fn short_reports() {
+        assert_eq!(Validation::Safe, validate_report(&[]));
+        assert_eq!(Validation::Safe, validate_report(&[7]));
+        assert_eq!(Validation::Safe, validate_report_dampened(&[], 1));
+        assert_eq!(Validation::Safe, validate_report_dampened(&[7], 0));
+        assert_eq!(
+            Validation::Dampened { removed: vec![1] },
+            validate_report_dampened(&[7, 7], 1),
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Ok(vec![vec![1, 2], vec![], vec![3]]), parse_reports(&["1 2", "", " 3 "]));
+
+        let err = |line, token: &str, kind| Err(ParseError { line, token: token.to_string(), kind });
+        assert_eq!(err(2, "-4", LevelError::Negative), parse_reports(&["1 2", "3 -4"]));
+        assert_eq!(err(1, "4294967296", LevelError::OutOfRange), parse_reports(&["4294967296"]));
+        assert_eq!(err(3, "x", LevelError::NotANumber), parse_reports(&["1", "2", "3 x"]));
+        assert_eq!(err(1, "-", LevelError::NotANumber), parse_reports(&["-"]));
+    }
+
+    #[test]
+    fn multiple_removals() {
+        assert!(!validate_report_dampened(&[1, 9, 9, 2, 3], 1).is_safe());
+        assert!(validate_report_dampened(&[1, 9, 9, 2, 3], 2).is_safe());
+        assert!(validate_report_dampened(&[5, 1, 9, 2, 3], 2).is_safe());
+    }
+}
@@ -0,0 +1,226 @@
+//! Audit of how a memory dump was interpreted.
+//!
+//! Runs the dump through an interpreter and records where every conditional
+//! call is and whether it ran, which stretches of the dump were disabled, and
+//! fragments that look like calls but weren't recognised. Needs the whole dump
+//! in memory, unlike `Interpreter::run_stream`.
+
+use std::{collections::HashSet, fmt};
+
+use crate::{interpreter::{Call, Interpreter}, lexer::{tokenize, Token, TokenKind}, value::Value, Result};
+
+/// Longest stretch after an instruction name searched for a near miss.
+const NEAR_MISS_WINDOW: usize = 32;
+
+/// Position of a byte in the dump, line and column are 1-based.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{} (offset {})", self.line, self.column, self.offset)
+    }
+}
+
+/// Why a near miss wasn't recognised as a call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reason {
+    /// Would be a valid call with the whitespace removed, e.g. `mul ( 2 , 4 )`.
+    Whitespace,
+    /// Well formed, but with the wrong number of operands.
+    Arity,
+    /// Well formed, but an operand is too wide, too narrow or signed.
+    Operand,
+    /// Anything else, e.g. `mul(4*`.
+    Malformed,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Whitespace => write!(f, "contains whitespace"),
+            Reason::Arity => write!(f, "wrong number of operands"),
+            Reason::Operand => write!(f, "operand out of range"),
+            Reason::Malformed => write!(f, "malformed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearMiss {
+    pub location: Location,
+    pub text: String,
+    pub reason: Reason,
+}
+
+/// A recognised conditional call and whether it ran.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallRecord<T> {
+    pub location: Location,
+    pub call: Call<T>,
+    pub enabled: bool,
+}
+
+/// A stretch of the dump where conditional calls were skipped, from the
+/// start of the call that disabled them to the end of the call after which
+/// they ran again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis<T> {
+    pub calls: Vec<CallRecord<T>>,
+    pub disabled: Vec<Span>,
+    pub near_misses: Vec<NearMiss>,
+    pub total: T,
+}
+
+/// Byte offsets of the start of every line, for offset to line lookups.
+struct Lines(Vec<usize>);
+
+impl Lines {
+    fn new(src: &[u8]) -> Self {
+        let starts = std::iter::once(0)
+            .chain(src.iter().enumerate().filter(|(_, b)| **b == b'\n').map(|(i, _)| i + 1))
+            .collect();
+        Lines(starts)
+    }
+
+    fn locate(&self, offset: usize) -> Location {
+        let line = self.0.partition_point(|&start| start <= offset);
+        Location { offset, line, column: offset - self.0[line - 1] + 1 }
+    }
+}
+
+/// Execute `src` on `interp`, recording how each part was interpreted.
+pub fn analyze<T: Value>(interp: &mut Interpreter<T>, src: &[u8]) -> Result<Analysis<T>> {
+    let lines = Lines::new(src);
+    let calls = interp.parse(src)?;
+    let starts: HashSet<usize> = calls.iter().map(|c| c.offset).collect();
+
+    let mut records = Vec::new();
+    let mut disabled = Vec::new();
+    let mut disabled_at = None;
+
+    for call in calls {
+        let location = lines.locate(call.offset);
+        let end = lines.locate(call.offset + call.len);
+        let conditional = interp.is_conditional(&call.name);
+        let was_enabled = interp.state.condition.is_enabled();
+
+        let enabled = interp.execute(call.clone())?;
+        if conditional {
+            records.push(CallRecord { location, call, enabled });
+        }
+
+        /* Track the on/off transitions as regions */
+        match (was_enabled, interp.state.condition.is_enabled()) {
+            (true, false) => disabled_at = Some(location),
+            (false, true) => {
+                if let Some(start) = disabled_at.take() {
+                    disabled.push(Span { start, end });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(start) = disabled_at {
+        disabled.push(Span { start, end: lines.locate(src.len()) });
+    }
+
+    let near_misses = find_near_misses(interp, src, &starts)
+        .into_iter()
+        .map(|(offset, text, reason)| NearMiss { location: lines.locate(offset), text, reason })
+        .collect();
+
+    Ok(Analysis { calls: records, disabled, near_misses, total: interp.state.total })
+}
+
+/// Find instruction names followed by `(` (possibly after whitespace) that
+/// didn't parse as a call. `calls` holds the offsets of the calls that did.
+fn find_near_misses<T: Value>(interp: &Interpreter<T>, src: &[u8], calls: &HashSet<usize>) -> Vec<(usize, String, Reason)> {
+    let tokens = tokenize(src);
+    let mut misses = Vec::new();
+
+    for (i, ident) in tokens.iter().enumerate().filter(|(_, t)| t.kind == TokenKind::Ident) {
+        let Some(name) = interp.match_name(ident.text(src)) else {
+            continue;
+        };
+        let start = ident.end() - name.len();
+        if calls.contains(&start) {
+            continue;
+        }
+
+        /* Skip whitespace to find the `(` */
+        let rest = &tokens[i + 1..];
+        let Some(open) = rest.iter().position(|t| !is_whitespace(src, t)) else {
+            continue;
+        };
+        if rest[open].kind != TokenKind::LParen {
+            continue;
+        }
+
+        let (end, reason) = classify(interp, src, name, start, &rest[open + 1..]);
+        misses.push((start, String::from_utf8_lossy(&src[start..end]).into_owned(), reason));
+    }
+
+    misses
+}
+
+/// Work out why the call to `name` at `start` failed, given the tokens after
+/// its `(`. Returns the end of the fragment worth showing and the reason.
+fn classify<T: Value>(interp: &Interpreter<T>, src: &[u8], name: &str, start: usize, args: &[Token]) -> (usize, Reason) {
+    let limit = start + NEAR_MISS_WINDOW;
+    let mut operands = 0;
+    let mut expect_operand = true;
+    let mut spaced = false;
+
+    for tok in args {
+        if tok.end() > limit {
+            return (tok.offset.min(limit).max(start), Reason::Malformed);
+        }
+
+        match tok.kind {
+            _ if is_whitespace(src, tok) => spaced = true,
+            TokenKind::Number if expect_operand => {
+                operands += 1;
+                expect_operand = false;
+            }
+            TokenKind::Minus if expect_operand => {}
+            TokenKind::Comma if !expect_operand => expect_operand = true,
+            TokenKind::RParen if !expect_operand || operands == 0 => {
+                /* Shape is fine, so it's spacing, operand count or the operands themselves */
+                let reason = if spaced && respaced_parses(interp, &src[start..tok.end()]) {
+                    Reason::Whitespace
+                } else if Some(operands) != interp.arity(name) {
+                    Reason::Arity
+                } else {
+                    Reason::Operand
+                };
+                return (tok.end(), reason);
+            }
+            /* Stop at the first unexpected byte */
+            _ => return (tok.offset + 1, Reason::Malformed),
+        }
+    }
+
+    (args.last().map_or(src.len(), |t| t.end()).min(limit), Reason::Malformed)
+}
+
+/// Whether `fragment` is a single valid call once whitespace is removed.
+fn respaced_parses<T: Value>(interp: &Interpreter<T>, fragment: &[u8]) -> bool {
+    let compact: Vec<u8> = fragment.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    matches!(interp.parse(&compact).as_deref(), Ok([call]) if call.offset == 0 && call.len == compact.len())
+}
+
+fn is_whitespace(src: &[u8], tok: &Token) -> bool {
+    tok.kind == TokenKind::Other && tok.text(src).iter().all(|b| b.is_ascii_whitespace())
+}
@@ -0,0 +1,117 @@
+//! Policies deciding when conditional instructions run.
+//!
+//! `do()` and `don't()` only report what they saw, the policy decides what
+//! that means. `Toggle` is the puzzle's plain on/off switch.
+
+use std::fmt;
+
+pub trait Condition: fmt::Debug {
+    /// A `do()` was executed.
+    fn enable(&mut self);
+
+    /// A `don't()` was executed.
+    fn disable(&mut self);
+
+    /// Whether the next conditional instruction runs.
+    ///
+    /// Called exactly once per conditional instruction, so policies that
+    /// count instructions can update themselves here.
+    fn allow(&mut self) -> bool;
+
+    /// Whether a conditional instruction would run right now, without
+    /// counting it as one.
+    fn is_enabled(&self) -> bool;
+}
+
+/// `don't()` turns conditional instructions off until the next `do()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Toggle {
+    enabled: bool,
+}
+
+impl Default for Toggle {
+    fn default() -> Self {
+        Toggle { enabled: true }
+    }
+}
+
+impl Condition for Toggle {
+    fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    fn disable(&mut self) {
+        self.enabled = false;
+    }
+
+    fn allow(&mut self) -> bool {
+        self.enabled
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// `don't()` opens a disabled region and `do()` closes the innermost one,
+/// so regions nest. Instructions run only outside every region. A `do()`
+/// with no open region does nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Stack {
+    depth: usize,
+}
+
+impl Condition for Stack {
+    fn enable(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    fn disable(&mut self) {
+        self.depth += 1;
+    }
+
+    fn allow(&mut self) -> bool {
+        self.depth == 0
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.depth == 0
+    }
+}
+
+/// `don't()` skips only the next `count` conditional instructions. A `do()`
+/// cancels whatever is left of the countdown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Countdown {
+    count: usize,
+    remaining: usize,
+}
+
+impl Countdown {
+    pub fn new(count: usize) -> Self {
+        Countdown { count, remaining: 0 }
+    }
+}
+
+impl Condition for Countdown {
+    fn enable(&mut self) {
+        self.remaining = 0;
+    }
+
+    fn disable(&mut self) {
+        self.remaining = self.count;
+    }
+
+    fn allow(&mut self) -> bool {
+        if self.remaining == 0 {
+            return true;
+        }
+
+        self.remaining -= 1;
+        false
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.remaining == 0
+    }
+}
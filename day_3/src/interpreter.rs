@@ -0,0 +1,362 @@
+//! Interpreter for the corrupted memory instruction language.
+//!
+//! Instructions are looked up in a table, so new ones can be registered
+//! without touching the parser. A call is an identifier ending in a known
+//! instruction name, immediately followed by `(`, a comma separated list of
+//! operands and `)`, with no whitespace anywhere. By default operands are 1-3
+//! digits with no sign, see `Operands`.
+
+use std::{collections::HashMap, io::Read};
+
+use crate::{condition::{Condition, Toggle}, lexer::{tokenize, Token, TokenKind}, value::Value, Result};
+
+/// Which operands are accepted.
+///
+/// The default matches the puzzle's `[0-9]{1,3}`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Operands {
+    pub min_digits: usize,
+    pub max_digits: usize,
+    /// Accept a leading `-`. Needs a signed `Value` type, see `check`.
+    pub signed: bool,
+}
+
+impl Default for Operands {
+    fn default() -> Self {
+        Operands { min_digits: 1, max_digits: 3, signed: false }
+    }
+}
+
+impl Operands {
+    /// Fail on settings that can't match anything as asked with `T` operands,
+    /// rather than quietly skipping every call.
+    pub fn check<T: Value>(&self) -> Result<()> {
+        if self.signed && !T::SIGNED {
+            return Err(format!("Signed operands need a signed accumulator, not {}", std::any::type_name::<T>()).into());
+        }
+        if self.min_digits > self.max_digits {
+            return Err(format!("Operands can't have at least {} digits and at most {}", self.min_digits, self.max_digits).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Machine state the instructions operate on.
+#[derive(Debug)]
+pub struct State<T> {
+    pub total: T,
+    /// Decides whether conditional instructions run.
+    pub condition: Box<dyn Condition>,
+}
+
+impl<T: Value> Default for State<T> {
+    fn default() -> Self {
+        State { total: T::default(), condition: Box::new(Toggle::default()) }
+    }
+}
+
+pub type Handler<T> = fn(&mut State<T>, &[T]) -> Result<()>;
+
+struct Instruction<T> {
+    arity: usize,
+    /// Only run when `State::condition` allows it.
+    conditional: bool,
+    handler: Handler<T>,
+}
+
+impl<T> Clone for Instruction<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Instruction<T> {}
+
+/// A recognised instruction call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call<T> {
+    pub name: String,
+    pub args: Vec<T>,
+    /// Byte offset of the first character of the instruction name.
+    pub offset: usize,
+    pub len: usize,
+}
+
+/// Result of trying to parse a call at a given token.
+enum Attempt<T> {
+    /// A call, and the number of tokens it spans.
+    Call(Call<T>, usize),
+    NoMatch,
+    /// Can't tell yet, the call may continue past the end of the buffer.
+    Incomplete,
+    /// An otherwise well formed operand doesn't fit in `T`, at this offset.
+    Overflow(usize),
+}
+
+/// One recognised call and whether it was executed or skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry<T> {
+    pub call: Call<T>,
+    pub executed: bool,
+}
+
+pub struct Interpreter<T> {
+    table: HashMap<String, Instruction<T>>,
+    pub state: State<T>,
+    pub operands: Operands,
+    trace: Option<Vec<TraceEntry<T>>>,
+}
+
+impl<T: Value> Interpreter<T> {
+    /// An interpreter with no instructions registered.
+    pub fn new() -> Self {
+        Interpreter {
+            table: HashMap::new(),
+            state: State::default(),
+            operands: Operands::default(),
+            trace: None,
+        }
+    }
+
+    /// An interpreter with the puzzle's `mul`, `do` and `don't`.
+    pub fn puzzle() -> Self {
+        let mut interp = Self::new();
+        interp.register("mul", 2, true, |s, a| {
+            s.total = a[0].checked_mul(a[1])
+                .and_then(|p| s.total.checked_add(p))
+                .ok_or("arithmetic overflow")?;
+            Ok(())
+        });
+        interp.register("do", 0, false, |s, _| {
+            s.condition.enable();
+            Ok(())
+        });
+        interp.register("don't", 0, false, |s, _| {
+            s.condition.disable();
+            Ok(())
+        });
+        interp
+    }
+
+    /// Add (or replace) an instruction.
+    ///
+    /// `conditional` instructions only run when `State::condition` allows it.
+    pub fn register(&mut self, name: &str, arity: usize, conditional: bool, handler: Handler<T>) {
+        self.table.insert(name.to_string(), Instruction { arity, conditional, handler });
+    }
+
+    /// Record every recognised call from now on, see `trace()`.
+    pub fn enable_trace(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    pub fn trace(&self) -> &[TraceEntry<T>] {
+        self.trace.as_deref().unwrap_or_default()
+    }
+
+    /// Parse and execute every call in `src`, returning the running total.
+    #[cfg(test)]
+    pub fn run(&mut self, src: &[u8]) -> Result<T> {
+        for call in self.parse(src)? {
+            self.execute(call)?;
+        }
+
+        Ok(self.state.total)
+    }
+
+    /// Like `run`, but reads `reader` in `chunk_size` byte chunks.
+    ///
+    /// A call split across two chunks is held back and finished once the
+    /// next chunk arrives, so only the current chunk plus the tail of one
+    /// unfinished call is ever in memory. State carries over between chunks
+    /// and call offsets are relative to the start of the stream.
+    pub fn run_stream<R: Read>(&mut self, mut reader: R, chunk_size: usize) -> Result<T> {
+        let mut chunk = vec![0; chunk_size.max(1)];
+        let mut buf = Vec::new();
+        let mut base = 0;
+
+        loop {
+            let n = reader.read(&mut chunk)?;
+            let eof = n == 0;
+            buf.extend_from_slice(&chunk[..n]);
+
+            let (calls, consumed) = self.parse_from(&buf, base, eof)?;
+            for call in calls {
+                self.execute(call)?;
+            }
+
+            if eof {
+                break;
+            }
+
+            buf.drain(..consumed);
+            base += consumed;
+        }
+
+        Ok(self.state.total)
+    }
+
+    /// Find every well formed call to a registered instruction in `src`.
+    pub fn parse(&self, src: &[u8]) -> Result<Vec<Call<T>>> {
+        Ok(self.parse_from(src, 0, true)?.0)
+    }
+
+    /// Like `parse`, for a buffer that starts `base` bytes into the stream.
+    ///
+    /// Unless `eof` is set, parsing stops at the first call that might still
+    /// continue past the end of `src`. Returns the calls plus how many bytes
+    /// of `src` are done with; the rest must be parsed again with more input.
+    fn parse_from(&self, src: &[u8], base: usize, eof: bool) -> Result<(Vec<Call<T>>, usize)> {
+        let tokens = tokenize(src);
+        let mut calls = Vec::new();
+
+        let mut i = 0;
+        while i < tokens.len() {
+            match self.parse_call(src, &tokens[i..], eof) {
+                Attempt::Call(mut call, used) => {
+                    call.offset += base;
+                    calls.push(call);
+                    i += used;
+                }
+                Attempt::NoMatch => i += 1,
+                Attempt::Incomplete => {
+                    /* Only the last `longest name` bytes of an identifier can
+                     * matter, so don't hold on to more than that. */
+                    let ident = tokens[i];
+                    let keep = self.table.keys().map(|k| k.len()).max().unwrap_or(0);
+                    return Ok((calls, ident.offset.max(ident.end().saturating_sub(keep))));
+                }
+                Attempt::Overflow(offset) => {
+                    return Err(format!("Operand at offset {} does not fit the accumulator", base + offset).into());
+                }
+            }
+        }
+
+        Ok((calls, src.len()))
+    }
+
+    /// Execute a single call, returning whether it actually ran.
+    pub fn execute(&mut self, call: Call<T>) -> Result<bool> {
+        let instr = *self.table.get(&call.name).ok_or_else(|| format!("Unknown instruction: {}", call.name))?;
+
+        let executed = !instr.conditional || self.state.condition.allow();
+        if executed {
+            (instr.handler)(&mut self.state, &call.args)
+                .map_err(|e| format!("{} at offset {}: {}", call.name, call.offset, e))?;
+        }
+
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry { call, executed });
+        }
+
+        Ok(executed)
+    }
+
+    /// Operand count of a registered instruction.
+    pub fn arity(&self, name: &str) -> Option<usize> {
+        self.table.get(name).map(|instr| instr.arity)
+    }
+
+    /// Whether a registered instruction is skipped while disabled.
+    pub fn is_conditional(&self, name: &str) -> bool {
+        self.table.get(name).is_some_and(|instr| instr.conditional)
+    }
+
+    /// Longest registered instruction name that `ident` ends with.
+    pub fn match_name(&self, ident: &[u8]) -> Option<&str> {
+        self.table.keys()
+            .filter(|name| ident.ends_with(name.as_bytes()))
+            .max_by_key(|name| name.len())
+            .map(|name| name.as_str())
+    }
+
+    /// Try to parse a call starting at the first token.
+    ///
+    /// Unless `eof` is set, running out of tokens, or depending on an
+    /// identifier or number that touches the end of `src` (and so might grow),
+    /// gives `Incomplete` rather than a verdict.
+    fn parse_call(&self, src: &[u8], tokens: &[Token], eof: bool) -> Attempt<T> {
+        let Some(ident) = tokens.first().filter(|t| t.kind == TokenKind::Ident) else {
+            return Attempt::NoMatch;
+        };
+
+        if !eof && ident.end() == src.len() {
+            return Attempt::Incomplete;
+        }
+        let Some(name) = self.match_name(ident.text(src)) else {
+            return Attempt::NoMatch;
+        };
+        let arity = self.table[name].arity;
+
+        let (args, close) = match self.parse_args(src, tokens, arity, eof) {
+            Ok(parsed) => parsed,
+            Err(attempt) => return attempt,
+        };
+
+        if args.len() != arity {
+            return Attempt::NoMatch;
+        }
+
+        let offset = ident.end() - name.len();
+        let call = Call { name: name.to_string(), args, offset, len: tokens[close].end() - offset };
+
+        Attempt::Call(call, close + 1)
+    }
+
+    /// Parse `(a,b,...)` following the identifier at `tokens[0]`, returning
+    /// the operands and the index of the closing paren.
+    fn parse_args(&self, src: &[u8], tokens: &[Token], arity: usize, eof: bool) -> std::result::Result<(Vec<T>, usize), Attempt<T>> {
+        let at_end = |tok: &Token| !eof && tok.end() == src.len();
+        let next = |i: usize| match tokens.get(i) {
+            Some(tok) => Ok(tok),
+            None if eof => Err(Attempt::NoMatch),
+            None => Err(Attempt::Incomplete),
+        };
+
+        if next(1)?.kind != TokenKind::LParen {
+            return Err(Attempt::NoMatch);
+        }
+
+        let mut args = Vec::new();
+        let mut i = 2;
+        loop {
+            let tok = next(i)?;
+            match tok.kind {
+                /* Only reachable straight after the `(` */
+                TokenKind::RParen if args.is_empty() => return Ok((args, i)),
+                TokenKind::Minus | TokenKind::Number if args.len() < arity => {
+                    let negative = tok.kind == TokenKind::Minus;
+                    if negative {
+                        if !self.operands.signed || !T::SIGNED {
+                            return Err(Attempt::NoMatch);
+                        }
+                        i += 1;
+                    }
+
+                    let digits = next(i)?;
+                    /* Can only get longer, so too wide is final */
+                    if digits.kind != TokenKind::Number || digits.len > self.operands.max_digits {
+                        return Err(Attempt::NoMatch);
+                    }
+                    if at_end(digits) {
+                        return Err(Attempt::Incomplete);
+                    }
+                    if digits.len < self.operands.min_digits {
+                        return Err(Attempt::NoMatch);
+                    }
+
+                    let value = T::from_digits(negative, digits.text(src)).ok_or(Attempt::Overflow(tok.offset))?;
+                    args.push(value);
+
+                    match next(i + 1)?.kind {
+                        TokenKind::Comma => i += 2,
+                        TokenKind::RParen => return Ok((args, i + 1)),
+                        _ => return Err(Attempt::NoMatch),
+                    }
+                }
+                _ => return Err(Attempt::NoMatch),
+            }
+        }
+    }
+}
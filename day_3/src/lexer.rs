@@ -0,0 +1,86 @@
+//! Tokenizer for the corrupted memory instruction language.
+//!
+//! Works on raw bytes rather than `str` so a dump does not have to be valid
+//! UTF-8. Every byte of the input ends up in exactly one token.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    /// A run of letters, `_` or `'` (so `don't` is a single identifier).
+    Ident,
+    /// A run of ASCII digits.
+    Number,
+    LParen,
+    RParen,
+    Comma,
+    Minus,
+    /// A run of bytes that can't start any other token.
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte offset of the token within the lexed input.
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl Token {
+    pub fn end(&self) -> usize {
+        self.offset + self.len
+    }
+
+    pub fn text<'a>(&self, src: &'a [u8]) -> &'a [u8] {
+        &src[self.offset..self.end()]
+    }
+}
+
+pub struct Lexer<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a [u8]) -> Self {
+        Lexer { src, pos: 0 }
+    }
+}
+
+fn is_ident(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_' || b == b'\''
+}
+
+fn kind_of(b: u8) -> TokenKind {
+    match b {
+        b'(' => TokenKind::LParen,
+        b')' => TokenKind::RParen,
+        b',' => TokenKind::Comma,
+        b'-' => TokenKind::Minus,
+        b if b.is_ascii_digit() => TokenKind::Number,
+        b if is_ident(b) => TokenKind::Ident,
+        _ => TokenKind::Other,
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let start = self.pos;
+        let kind = kind_of(*self.src.get(start)?);
+        self.pos += 1;
+
+        /* Punctuation is always a single byte, everything else is a run */
+        if !matches!(kind, TokenKind::LParen | TokenKind::RParen | TokenKind::Comma | TokenKind::Minus) {
+            while self.pos < self.src.len() && kind_of(self.src[self.pos]) == kind {
+                self.pos += 1;
+            }
+        }
+
+        Some(Token { kind, offset: start, len: self.pos - start })
+    }
+}
+
+pub fn tokenize(src: &[u8]) -> Vec<Token> {
+    Lexer::new(src).collect()
+}
@@ -1,6 +1,14 @@
 use std::{error, fs::File, io::Read};
 
-use regex::Regex;
+use condition::{Condition, Countdown, Stack, Toggle};
+use interpreter::{Interpreter, Operands};
+use value::Value;
+
+mod analysis;
+mod condition;
+mod interpreter;
+mod lexer;
+mod value;
 
 // This lets us bubble up all errors to main() regardless of type
 type Error = Box<dyn error::Error>;
@@ -8,43 +16,355 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 const INPUT: &str = "data/day3_1.txt";
 
+/// Bytes read from the input at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Command line options.
+struct Config {
+    trace: bool,
+    analyze: bool,
+    extended: bool,
+    chunk_size: usize,
+    operands: Operands,
+    condition: Box<dyn Condition>,
+}
+
 fn main() -> Result<()> {
     let cwd = std::env::current_dir()?;
     // println!("The current directory is {}", cwd.display());
 
+    let mut config = Config {
+        trace: false,
+        analyze: false,
+        extended: false,
+        chunk_size: CHUNK_SIZE,
+        operands: Operands::default(),
+        condition: Box::new(Toggle::default()),
+    };
+    let mut accumulator = String::from("u64");
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} requires a value", arg));
+        match arg.as_str() {
+            "--trace" => config.trace = true,
+            "--analyze" => config.analyze = true,
+            "--extended" => config.extended = true,
+            "--signed" => config.operands.signed = true,
+            "--chunk" => config.chunk_size = value()?.parse()?,
+            "--min-digits" => config.operands.min_digits = value()?.parse()?,
+            "--max-digits" => config.operands.max_digits = value()?.parse()?,
+            "--accumulator" => accumulator = value()?,
+            "--condition" => config.condition = parse_condition(&value()?)?,
+            _ => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
     /* Open Input file */
-    let mut in_file = File::open(cwd.join(INPUT))?;
+    let in_file = File::open(cwd.join(INPUT))?;
 
-    let mut input = String::new();
-    in_file.read_to_string(&mut input)?;
+    match accumulator.as_str() {
+        "u64" => run::<u64>(in_file, config),
+        "u128" => run::<u128>(in_file, config),
+        "i64" => run::<i64>(in_file, config),
+        "i128" => run::<i128>(in_file, config),
+        _ => Err(format!("Unknown accumulator: {}", accumulator).into()),
+    }
+}
 
-    let total = run_calculations(&input)?;
+/// Parse `toggle`, `stack` or `countdown:N`.
+fn parse_condition(arg: &str) -> Result<Box<dyn Condition>> {
+    match arg.split_once(':') {
+        None if arg == "toggle" => Ok(Box::new(Toggle::default())),
+        None if arg == "stack" => Ok(Box::new(Stack::default())),
+        Some(("countdown", n)) => Ok(Box::new(Countdown::new(n.parse()?))),
+        _ => Err(format!("Unknown condition: {}", arg).into()),
+    }
+}
+
+fn run<T: Value>(mut in_file: File, config: Config) -> Result<()> {
+    config.operands.check::<T>()?;
+
+    let mut interp = Interpreter::<T>::puzzle();
+    interp.operands = config.operands;
+    interp.state.condition = config.condition;
+    if config.extended {
+        register_extended(&mut interp);
+    }
+    if config.trace {
+        interp.enable_trace();
+    }
+
+    if config.analyze {
+        let mut input = Vec::new();
+        in_file.read_to_end(&mut input)?;
+        return print_analysis(&mut interp, &input);
+    }
+
+    /* Stream the file rather than reading it all in, dumps can be huge */
+    let total = interp.run_stream(in_file, config.chunk_size)?;
+
+    for entry in interp.trace() {
+        let args: Vec<String> = entry.call.args.iter().map(|a| a.to_string()).collect();
+        println!("{:>8}  {}({}){}", entry.call.offset, entry.call.name, args.join(","),
+            if entry.executed { "" } else { "  [skipped]" });
+    }
 
     println!("Result: {}", total);
 
     Ok(())
 }
 
-fn run_calculations(input: &str) -> Result<u32> {
-    let mut total = 0;
-    let mut should_do = true;
-
-    // let re = Regex::new(r"mul\(([0-9]{1,3}),([0-9]{1,3})\)")?;
-    let re = Regex::new(r"(?:mul\(([0-9]{1,3}),([0-9]{1,3})\))|(?:do\(\))|(?:don't\(\))")?;
-    for caps in re.captures_iter(&input) {
-        match &caps[0] {
-            "do()" => should_do = true,
-            "don't()" =>  should_do = false,
-            _ => {
-                if should_do {
-                    let l: u32 = caps[1].parse()?;
-                    let r: u32 = caps[2].parse()?;
-
-                    total += l * r;
+fn print_analysis<T: Value>(interp: &mut Interpreter<T>, input: &[u8]) -> Result<()> {
+    let report = analysis::analyze(interp, input)?;
+
+    println!("Calls:");
+    for record in &report.calls {
+        let args: Vec<String> = record.call.args.iter().map(|a| a.to_string()).collect();
+        println!("  {:<24} {}({})  {}", record.location.to_string(), record.call.name, args.join(","),
+            if record.enabled { "enabled" } else { "disabled" });
+    }
+
+    println!("Disabled regions:");
+    for span in &report.disabled {
+        println!("  {} - {}", span.start, span.end);
+    }
+
+    println!("Near misses:");
+    for miss in &report.near_misses {
+        println!("  {:<24} {:?}  {}", miss.location.to_string(), miss.text, miss.reason);
+    }
+
+    println!("Result: {}", report.total);
+
+    Ok(())
+}
+
+/// Register `add(a,b)` and `sub(a,b)` on top of the puzzle instructions.
+///
+/// Both add their result to the total, like `mul` does.
+fn register_extended<T: Value>(interp: &mut Interpreter<T>) {
+    interp.register("add", 2, true, |s, a| {
+        s.total = a[0].checked_add(a[1])
+            .and_then(|r| s.total.checked_add(r))
+            .ok_or("arithmetic overflow")?;
+        Ok(())
+    });
+    interp.register("sub", 2, true, |s, a| {
+        s.total = a[0].checked_sub(a[1])
+            .and_then(|r| s.total.checked_add(r))
+            .ok_or("arithmetic overflow")?;
+        Ok(())
+    });
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use regex::Regex;
+
+    use super::*;
+
+    fn puzzle() -> Interpreter<u64> {
+        Interpreter::puzzle()
+    }
+
+    fn run_calculations(input: &str) -> Result<u64> {
+        puzzle().run(input.as_bytes())
+    }
+
+    /// The original single regex implementation, kept as a reference.
+    fn run_calculations_regex(input: &str) -> Result<u64> {
+        let mut total = 0;
+        let mut should_do = true;
+
+        let re = Regex::new(r"(?:mul\(([0-9]{1,3}),([0-9]{1,3})\))|(?:do\(\))|(?:don't\(\))")?;
+        for caps in re.captures_iter(input) {
+            match &caps[0] {
+                "do()" => should_do = true,
+                "don't()" =>  should_do = false,
+                _ => {
+                    if should_do {
+                        let l: u64 = caps[1].parse()?;
+                        let r: u64 = caps[2].parse()?;
+
+                        total += l * r;
+                    }
                 }
             }
         }
+
+        Ok(total)
+    }
+
+    #[test]
+    fn example_input() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        assert_eq!(48, run_calculations(input).unwrap());
+    }
+
+    #[test]
+    fn matches_regex() {
+        let mut rng = StdRng::seed_from_u64(2024);
+
+        let pieces = ["mul", "do", "don't", "(", ")", ",", "1", "23", "4567", "x", " ", "'", "n't"];
+        for _ in 0..500 {
+            let input: String = (0..60).map(|_| pieces[rng.gen_range(0..pieces.len())]).collect();
+            assert_eq!(run_calculations_regex(&input).unwrap(), run_calculations(&input).unwrap(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn trace() {
+        let input = b"mul(2,4)don't()mul(5,5)do()";
+        let mut interp = puzzle();
+        interp.enable_trace();
+        interp.run(input).unwrap();
+
+        let trace: Vec<(&str, usize, bool)> = interp.trace().iter()
+            .map(|e| (e.call.name.as_str(), e.call.offset, e.executed))
+            .collect();
+        assert_eq!(vec![("mul", 0, true), ("don't", 8, true), ("mul", 15, false), ("do", 23, true)], trace);
     }
 
-    Ok(total)
+    #[test]
+    fn stream_chunk_boundaries() {
+        let input = b"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+        let mut whole = puzzle();
+        whole.enable_trace();
+        whole.run(input).unwrap();
+
+        /* Every possible split point, including splitting `don't` and `mul` */
+        for chunk_size in 1..=input.len() {
+            let mut streamed = puzzle();
+            streamed.enable_trace();
+
+            assert_eq!(48, streamed.run_stream(&input[..], chunk_size).unwrap());
+            assert_eq!(whole.trace(), streamed.trace(), "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn stream_bounded_carry() {
+        /* A long identifier or run of operands must not pile up in the buffer */
+        let mut input = vec![b'x'; 10_000];
+        input.extend_from_slice(b"mul(1,2,3,4,5,6,7,8,9)mul(3,4)");
+
+        for chunk_size in [1, 7, 64] {
+            assert_eq!(12, puzzle().run_stream(&input[..], chunk_size).unwrap());
+        }
+    }
+
+    #[test]
+    fn extended_instructions() {
+        let mut interp = puzzle();
+        register_extended(&mut interp);
+        assert_eq!(15, interp.run(b"add(3,4)mul(2,3)sub(5,3)").unwrap());
+        assert!(interp.run(b"sub(1,100)").is_err());
+    }
+
+    #[test]
+    fn analysis() {
+        use analysis::*;
+
+        let input = b"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)\n+mul(32,64](mul(11,8)undo()?mul(8,5))mul(4*mul ( 2 , 4 )mul(1,2,3)mul(1234,5)";
+        let report = analyze(&mut puzzle(), input).unwrap();
+
+        let calls: Vec<(usize, usize, bool)> = report.calls.iter()
+            .map(|r| (r.location.line, r.location.column, r.enabled))
+            .collect();
+        assert_eq!(vec![(1, 2, true), (1, 29, false), (2, 13, false), (2, 29, true)], calls);
+
+        let disabled: Vec<(usize, usize)> = report.disabled.iter().map(|s| (s.start.offset, s.end.offset)).collect();
+        assert_eq!(vec![(20, 64)], disabled);
+
+        let misses: Vec<(&str, Reason)> = report.near_misses.iter().map(|m| (m.text.as_str(), m.reason)).collect();
+        assert_eq!(vec![
+            ("mul(32,64]", Reason::Malformed),
+            ("mul(4*", Reason::Malformed),
+            ("mul ( 2 , 4 )", Reason::Whitespace),
+            ("mul(1,2,3)", Reason::Arity),
+            ("mul(1234,5)", Reason::Operand),
+        ], misses);
+
+        assert_eq!(48, report.total);
+    }
+
+    #[test]
+    fn conditions() {
+        let input = b"mul(1,1)don't()mul(1,2)don't()mul(1,3)do()mul(1,4)do()mul(1,5)mul(1,6)";
+
+        let run = |condition: Box<dyn Condition>| {
+            let mut interp = puzzle();
+            interp.state.condition = condition;
+            interp.run(input).unwrap()
+        };
+
+        assert_eq!(1 + 4 + 5 + 6, run(parse_condition("toggle").unwrap()));
+        /* The first do() only closes the inner region */
+        assert_eq!(1 + 5 + 6, run(parse_condition("stack").unwrap()));
+        /* Each don't() skips just the next call, do() is a no-op here */
+        assert_eq!(1 + 4 + 5 + 6, run(parse_condition("countdown:1").unwrap()));
+        assert_eq!(1 + 4 + 5 + 6, run(Box::new(Countdown::new(5))));
+        assert_eq!(1 + 2 + 3 + 4 + 5 + 6, run(Box::new(Countdown::new(0))));
+        assert!(parse_condition("countdown").is_err());
+    }
+
+    #[test]
+    fn countdown_regions() {
+        let mut interp = puzzle();
+        interp.state.condition = Box::new(Countdown::new(2));
+        let report = analysis::analyze(&mut interp, b"don't()mul(1,2)mul(1,3)mul(1,4)").unwrap();
+
+        let enabled: Vec<bool> = report.calls.iter().map(|r| r.enabled).collect();
+        assert_eq!(vec![false, false, true], enabled);
+
+        let disabled: Vec<(usize, usize)> = report.disabled.iter().map(|s| (s.start.offset, s.end.offset)).collect();
+        assert_eq!(vec![(0, 23)], disabled);
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let mut interp = Interpreter::<u32>::puzzle();
+        interp.operands.max_digits = 5;
+        let err = interp.run(b"mul(65535,65535)mul(65535,65535)").unwrap_err();
+        assert_eq!("mul at offset 16: arithmetic overflow", err.to_string());
+
+        /* u128 has plenty of room for the same input */
+        let mut interp = Interpreter::<u128>::puzzle();
+        interp.operands.max_digits = 5;
+        assert_eq!(2 * 65535 * 65535, interp.run(b"mul(65535,65535)mul(65535,65535)").unwrap());
+
+        /* An operand too big for the type is an error, not a silent skip */
+        let mut interp = Interpreter::<u64>::puzzle();
+        interp.operands.max_digits = 30;
+        assert!(interp.run(b"mul(99999999999999999999,1)").is_err());
+    }
+
+    #[test]
+    fn operand_widths() {
+        /* Default is the puzzle's 1-3 digits */
+        assert_eq!(6, run_calculations("mul(2,3)mul(1000,1)mul(-2,3)").unwrap());
+
+        let mut interp = puzzle();
+        interp.operands = Operands { min_digits: 2, max_digits: 4, signed: false };
+        assert_eq!(1000, interp.run(b"mul(2,3)mul(1000,01)").unwrap());
+
+        let backwards = Operands { min_digits: 4, max_digits: 2, signed: false };
+        assert_eq!("Operands can't have at least 4 digits and at most 2", backwards.check::<u64>().unwrap_err().to_string());
+    }
+
+    #[test]
+    fn signed_operands() {
+        let mut interp = Interpreter::<i64>::puzzle();
+        interp.operands.signed = true;
+        assert_eq!(-6 + 20, interp.run(b"mul(-2,3)mul(-4,-5)mul(--1,2)mul(-,2)").unwrap());
+
+        /* Negative operands can't go in an unsigned accumulator */
+        let signed = Operands { signed: true, ..Operands::default() };
+        assert!(signed.check::<i64>().is_ok());
+        assert_eq!("Signed operands need a signed accumulator, not u64", signed.check::<u64>().unwrap_err().to_string());
+    }
 }
@@ -0,0 +1,49 @@
+//! Integer types the interpreter can use for operands and the running total.
+//!
+//! All arithmetic goes through the checked operations here, so overflow is
+//! reported instead of wrapping or panicking.
+
+use std::fmt;
+
+pub trait Value: Copy + Default + PartialEq + fmt::Debug + fmt::Display {
+    /// Whether negative operands can be represented at all.
+    const SIGNED: bool;
+
+    /// Build a value from ASCII digits, `None` if it doesn't fit.
+    fn from_digits(negative: bool, digits: &[u8]) -> Option<Self>;
+
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_value {
+    ($($t:ty => $signed:expr),*) => {$(
+        impl Value for $t {
+            const SIGNED: bool = $signed;
+
+            fn from_digits(negative: bool, digits: &[u8]) -> Option<Self> {
+                /* Accumulate towards the sign so the most negative value fits */
+                digits.iter().try_fold(0 as $t, |n, d| {
+                    let d = (d - b'0') as $t;
+                    let n = n.checked_mul(10)?;
+                    if negative { n.checked_sub(d) } else { n.checked_add(d) }
+                })
+            }
+
+            fn checked_add(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_add(self, rhs)
+            }
+
+            fn checked_sub(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_sub(self, rhs)
+            }
+
+            fn checked_mul(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_mul(self, rhs)
+            }
+        }
+    )*};
+}
+
+impl_value!(u32 => false, u64 => false, u128 => false, i64 => true, i128 => true);
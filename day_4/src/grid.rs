@@ -0,0 +1,72 @@
+//! Rectangular grid of letters.
+//!
+//! Cells are `char`s, so letters outside ASCII take one cell each no matter
+//! how many bytes they are in UTF-8. Combining characters are not merged with
+//! the letter before them, so accented letters should be precomposed.
+
+use crate::Result;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid {
+    cells: Vec<char>,
+    width: usize,
+    height: usize,
+}
+
+impl Grid {
+    /// Build a grid from its rows, which must all be the same length.
+    pub fn new<S: AsRef<str>>(rows: &[S]) -> Result<Self> {
+        let width = rows.first().map_or(0, |r| r.as_ref().chars().count());
+        let mut cells = Vec::with_capacity(width * rows.len());
+
+        for (y, row) in rows.iter().enumerate() {
+            let len = cells.len();
+            cells.extend(row.as_ref().chars());
+
+            if cells.len() - len != width {
+                return Err(format!("Row {} is {} letters wide, expected {}", y + 1, cells.len() - len, width).into());
+            }
+        }
+
+        Ok(Grid { cells, width, height: rows.len() })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Check `x`,`y` lies within the grid, converting to indices if so.
+    pub fn contains(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        if x >= 0 && (x as usize) < self.width && y >= 0 && (y as usize) < self.height {
+            Some((x as usize, y as usize))
+        } else {
+            None
+        }
+    }
+
+    /// Wrap `x`,`y` around the edges, as if the grid were a torus.
+    pub fn wrap(&self, x: i32, y: i32) -> (usize, usize) {
+        (x.rem_euclid(self.width as i32) as usize, y.rem_euclid(self.height as i32) as usize)
+    }
+
+    /// `contains`, or `wrap` when `wrap` is set. Wrapping an empty grid
+    /// still finds nothing.
+    pub fn locate(&self, x: i32, y: i32, wrap: bool) -> Option<(usize, usize)> {
+        match wrap {
+            true if !self.cells.is_empty() => Some(self.wrap(x, y)),
+            _ => self.contains(x, y),
+        }
+    }
+
+    pub fn at(&self, x: usize, y: usize) -> char {
+        self.cells[y * self.width + x]
+    }
+
+    pub fn get(&self, x: i32, y: i32, wrap: bool) -> Option<char> {
+        self.locate(x, y, wrap).map(|(x, y)| self.at(x, y))
+    }
+}
@@ -0,0 +1,81 @@
+//! Small 2D patterns matched against the word search grid.
+//!
+//! A template is written row by row, rows separated by `/`, with `.` matching
+//! any letter. `M.S/.A./M.S` is one X-MAS.
+
+use crate::{grid::Grid, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+    /// (x, y, letter) for every non-wildcard cell, sorted, with the smallest
+    /// x and y both 0.
+    cells: Vec<(i32, i32, char)>,
+}
+
+impl Kernel {
+    pub fn parse(template: &str) -> Result<Self> {
+        let rows: Vec<&str> = template.split('/').collect();
+        let width = rows[0].chars().count();
+
+        if rows.iter().any(|r| r.chars().count() != width) {
+            return Err(format!("Kernel rows must all be the same length: {}", template).into());
+        }
+
+        let cells = rows.iter().enumerate()
+            .flat_map(|(y, row)| row.chars().enumerate().map(move |(x, c)| (x as i32, y as i32, c)))
+            .filter(|(_, _, c)| *c != '.')
+            .collect();
+
+        Kernel::new(cells).ok_or_else(|| format!("Kernel has no letters: {}", template).into())
+    }
+
+    fn new(mut cells: Vec<(i32, i32, char)>) -> Option<Self> {
+        let min_x = cells.iter().map(|c| c.0).min()?;
+        let min_y = cells.iter().map(|c| c.1).min()?;
+
+        for cell in &mut cells {
+            cell.0 -= min_x;
+            cell.1 -= min_y;
+        }
+        cells.sort();
+
+        Some(Kernel { cells })
+    }
+
+    /// Quarter turn clockwise.
+    fn rotate(&self) -> Self {
+        Kernel::new(self.cells.iter().map(|&(x, y, c)| (-y, x, c)).collect()).unwrap()
+    }
+
+    /// Mirror left to right.
+    fn reflect(&self) -> Self {
+        Kernel::new(self.cells.iter().map(|&(x, y, c)| (-x, y, c)).collect()).unwrap()
+    }
+
+    /// Every distinct rotation and reflection of the kernel, itself included.
+    pub fn orientations(&self) -> Vec<Kernel> {
+        let mut all: Vec<Kernel> = Vec::new();
+
+        for mut k in [self.clone(), self.reflect()] {
+            for _ in 0..4 {
+                if !all.contains(&k) {
+                    all.push(k.clone());
+                }
+                k = k.rotate();
+            }
+        }
+
+        all
+    }
+
+    /// Grid cells covered by the kernel's letters when its top left corner is
+    /// at `x`,`y`, if every letter matches.
+    pub fn match_at(&self, input: &Grid, x: i32, y: i32) -> Option<Vec<(usize, usize)>> {
+        self.cells.iter()
+            .map(|&(dx, dy, c)| {
+                let (nx, ny) = input.contains(x + dx, y + dy)?;
+                (input.at(nx, ny) == c).then_some((nx, ny))
+            })
+            .collect()
+    }
+}
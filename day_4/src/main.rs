@@ -1,4 +1,13 @@
-use std::{error, fs::File, io::{self, BufRead}};
+use std::{collections::HashSet, error, fs::File, io::{self, BufRead}};
+
+use grid::Grid;
+use kernel::Kernel;
+use trie::Trie;
+
+mod grid;
+mod kernel;
+mod render;
+mod trie;
 
 // This lets us bubble up all errors to main() regardless of type
 type Error = Box<dyn error::Error>;
@@ -9,6 +18,53 @@ const INPUT: &str = "data/day4_1.txt";
 const SEARCH_WORD: &str = "XMAS";
 const SEARCH_WORD2: &str = "MAS";
 
+const DIRECTIONS: [(i32, i32); 8] = [(1,0), (1,1), (0,1), (-1,1), (-1,0), (-1,-1), (0,-1), (1,-1)];  // (x, y) CCW in 45deg increments, starting in the east position.
+const KNIGHT_MOVES: [(i32, i32); 8] = [(2,1), (1,2), (-1,2), (-2,1), (-2,-1), (-1,-2), (1,-2), (2,-1)];  // (x, y) CCW, starting just south of east.
+
+/// How words are searched for.
+#[derive(Debug, Clone, PartialEq)]
+struct Search {
+    /// Step between consecutive letters of a word.
+    steps: Vec<(i32, i32)>,
+    /// Words running off one edge carry on from the opposite edge.
+    wrap: bool,
+    /// Matches of the same word may share cells. Without this a match is
+    /// only counted if none of its cells belong to an earlier one (in
+    /// row, column, step order).
+    overlapping: bool,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Search { steps: DIRECTIONS.to_vec(), wrap: false, overlapping: true }
+    }
+}
+
+/// One occurrence of a word, first letter at `x`,`y`, read along `dir`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct WordMatch {
+    x: usize,
+    y: usize,
+    dir: (i32, i32),
+    len: usize,
+}
+
+impl WordMatch {
+    /// Cells of the match, wrapped around the edges of `input` if it runs off
+    /// them.
+    fn cells(&self, input: &Grid) -> Vec<(usize, usize)> {
+        (0..self.len as i32)
+            .map(|i| input.wrap(self.x as i32 + i * self.dir.0, self.y as i32 + i * self.dir.1))
+            .collect()
+    }
+}
+
+/// One placement of a 2D pattern, identified by the (sorted) cells it covers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Placement {
+    cells: Vec<(usize, usize)>,
+}
+
 fn main() -> Result<()> {
     let cwd = std::env::current_dir()?;
     // println!("The current directory is {}", cwd.display());
@@ -16,13 +72,66 @@ fn main() -> Result<()> {
     /* Open Input file */
     let in_file = File::open(cwd.join(INPUT))?;
 
-    let input = parse_input(&in_file)?;
+    let input = Grid::new(&parse_input(&in_file)?)?;
+
+    /* `--words <file>` searches for every word in the file, one per line.
+     * `--kernel <template>` counts placements of a 2D pattern.
+     * `--render` draws the matches, `--colour` gives each its own colour.
+     * `--wrap`, `--knight`, `--step <dx,dy>` and `--no-overlap` change how
+     * words are searched for, see `Search`. */
+    let mut search = Search::default();
+    let mut words_file = None;
+    let mut kernels = Vec::new();
+    let mut render = false;
+    let mut colour = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--words" => words_file = Some(args.next().ok_or("--words requires a file")?),
+            "--kernel" => kernels.push(args.next().ok_or("--kernel requires a template")?),
+            "--render" => render = true,
+            "--colour" => colour = true,
+            "--wrap" => search.wrap = true,
+            "--knight" => search.steps.extend(KNIGHT_MOVES),
+            "--step" => search.steps.push(parse_step(&args.next().ok_or("--step requires a dx,dy vector")?)?),
+            "--no-overlap" => search.overlapping = false,
+            _ => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
 
-    let count = count_matches(&input, SEARCH_WORD);
-    let count2 = count_matches2(&input, SEARCH_WORD2);
+    let show = |cells: Vec<Vec<(usize, usize)>>| {
+        if render {
+            print!("{}", render::render(&input, &cells, colour));
+        }
+    };
 
-    println!("Count: {}", count);
-    println!("Count2: {}", count2);
+    for template in &kernels {
+        let kernel = Kernel::parse(template)?;
+        let placements = find_kernel(&input, &kernel);
+        println!("{}: {}", template, placements.len());
+        show(placements.into_iter().map(|p| p.cells).collect());
+    }
+    if !kernels.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(path) = words_file {
+        let words = parse_input(&File::open(cwd.join(path))?)?;
+        let words: Vec<&str> = words.iter().map(|w| w.trim()).filter(|w| !w.is_empty()).collect();
+
+        for (word, count) in words.iter().zip(count_words(&input, &words, &search)) {
+            println!("{}: {}", word, count);
+        }
+        return Ok(());
+    }
+
+    let matches = find_matches(&input, SEARCH_WORD, &search);
+    println!("Count: {}", matches.len());
+    show(matches.iter().map(|m| m.cells(&input)).collect());
+
+    let matches2 = find_matches2(&input, SEARCH_WORD2);
+    println!("Count2: {}", matches2.len());
+    show(matches2.into_iter().map(|p| p.cells).collect());
 
     Ok(())
 }
@@ -39,46 +148,80 @@ fn parse_input(f: &File) -> Result<Vec<String>> {
     Ok(input)
 }
 
-fn count_matches( input: &Vec<String>, word: &str) -> u32 {
-    let directions: Vec<(i32, i32)> = vec![(1,0), (1,1), (0,1), (-1,1), (-1,0), (-1,-1), (0,-1), (1,-1)];  // (x, y) CCW in 45deg increments, starting in the east position.
+/// Parse a `dx,dy` step vector.
+fn parse_step(s: &str) -> Result<(i32, i32)> {
+    let (dx, dy) = s.split_once(',').ok_or_else(|| format!("Step must be dx,dy: {}", s))?;
+    let step = (dx.trim().parse()?, dy.trim().parse()?);
 
-    let mut count = 0u32;
+    if step == (0, 0) {
+        return Err("Step must move".into());
+    }
+    Ok(step)
+}
 
-    for y in 0..input.len() {
-        for x in 0..input[y].len() {
-            for dir in &directions {
+/// Steps in `search` with duplicates removed, keeping the first of each.
+fn unique_steps(search: &Search) -> Vec<(i32, i32)> {
+    let mut steps = Vec::new();
+    for step in &search.steps {
+        if !steps.contains(step) {
+            steps.push(*step);
+        }
+    }
+    steps
+}
 
-                if find_word(&input, word, x, y, dir) {
-                    count += 1;
+/// Whether `found` counts, recording its cells in `used` if so. Always true
+/// when overlapping matches are allowed, without working out the cells.
+fn claim(search: &Search, used: &mut HashSet<(usize, usize)>, input: &Grid, found: &WordMatch) -> bool {
+    if search.overlapping {
+        return true;
+    }
+    let cells = found.cells(input);
+    if cells.iter().any(|c| used.contains(c)) {
+        return false;
+    }
+    used.extend(cells);
+    true
+}
+
+/// Find every occurrence of `word` along each of the search's steps.
+fn find_matches( input: &Grid, word: &str, search: &Search) -> Vec<WordMatch> {
+    let word: Vec<char> = word.chars().collect();
+    let steps = unique_steps(search);
+    let mut matches = Vec::new();
+    let mut used = HashSet::new();
+
+    for y in 0..input.height() {
+        for x in 0..input.width() {
+            for dir in &steps {
+
+                if find_word(input, &word, x, y, dir, search.wrap) {
+                    let found = WordMatch { x, y, dir: *dir, len: word.len() };
+                    if claim(search, &mut used, input, &found) {
+                        matches.push(found);
+                    }
                 }
             }
         }
     }
 
-    count
+    matches
 }
 
 // Search for `word` within `input` at the given rotation (`dir`) around `x`,`y`. With the
 //  first letter of `word` used as the rotation point.
-fn find_word( input: &Vec<String>, word: &str, x: usize, y: usize, dir: &(i32, i32) ) -> bool {
+fn find_word( input: &Grid, word: &[char], x: usize, y: usize, dir: &(i32, i32), wrap: bool ) -> bool {
     let mut matches = true;
 
-    for i in 0..word.len() {
+    for (i, &c) in word.iter().enumerate() {
         let x = x as i32;
         let y = y as i32;
 
         let dx = i as i32 * dir.0;
         let dy = i as i32 * dir.1;
 
-        /* Is index within input? */
-        if let Some((nx, ny)) = is_inside(&input, x + dx, y + dy) {
-            /* Check char for match */
-            if input[ny].as_bytes()[nx] != word.as_bytes()[i] {
-                matches = false;
-                break;
-            }
-        }
-        else {
+        /* Check char for match, off the grid never matches unless wrapping */
+        if input.get(x + dx, y + dy, wrap) != Some(c) {
             matches = false;
             break;
         }
@@ -87,33 +230,92 @@ fn find_word( input: &Vec<String>, word: &str, x: usize, y: usize, dir: &(i32, i
     matches
 }
 
-fn count_matches2( input: &Vec<String>, word: &str) -> u32 {
+/// Count every occurrence of each of `words` along each of the search's
+/// steps.
+///
+/// Rather than scanning the grid once per word, this walks a trie of the
+/// whole word list from each cell in each direction, so the grid is scanned
+/// once however long the list is. Counts are in the same order as `words`.
+/// Overlap is only checked between matches of the same word.
+fn count_words( input: &Grid, words: &[&str], search: &Search) -> Vec<u32> {
+    let trie = Trie::new(words);
+    let lengths: Vec<usize> = words.iter().map(|w| w.chars().count()).collect();
+    let mut counts = vec![0u32; words.len()];
+    let mut used = vec![HashSet::new(); words.len()];
+    let steps = unique_steps(search);
+
+    for y in 0..input.height() {
+        for x in 0..input.width() {
+            for &dir in &steps {
+                let mut node = trie.root();
+                let (mut cx, mut cy) = (x as i32, y as i32);
+
+                /* Follow the trie until the grid edge or a dead end. The trie
+                 * is no deeper than the longest word, so this ends even when
+                 * wrapping. */
+                while let Some((nx, ny)) = input.locate(cx, cy, search.wrap) {
+                    node = match trie.step(node, input.at(nx, ny)) {
+                        Some(next) => next,
+                        None => break,
+                    };
+
+                    for &word in trie.words(node) {
+                        let found = WordMatch { x, y, dir, len: lengths[word] };
+                        if claim(search, &mut used[word], input, &found) {
+                            counts[word] += 1;
+                        }
+                    }
+
+                    cx += dir.0;
+                    cy += dir.1;
+                }
+            }
+        }
+    }
+
+    counts
+}
+
+/// Find every X of two `word`s crossing at their centres.
+fn find_matches2( input: &Grid, word: &str) -> Vec<Placement> {
+    let word: Vec<char> = word.chars().collect();
     let directions: Vec<(i32, i32)> = vec![(1,1),(-1,1),(-1,-1),(1,-1)];  // north-east, north-west, south-west, south-east
 
-    let mut count = 0u32;
+    let mut placements = Vec::new();
 
-    for y in 0..input.len() {
-        for x in 0..input[y].len() {
-            let mut dirs_matched = 0;
+    for y in 0..input.height() {
+        for x in 0..input.width() {
+            let mut dirs_matched = Vec::new();
             for dir in &directions {
                 /* Search all 4 angled dirs, but only 2 can ever match within one kernel */
-                if find_word2(&input, word, x, y, dir) {
-                    dirs_matched += 1;
+                if find_word2(input, &word, x, y, dir) {
+                    dirs_matched.push(*dir);
                 }
             }
 
-            if dirs_matched >= 2 {
-                count += 1;
+            if dirs_matched.len() >= 2 {
+                /* Both words, each starting half a word back from the centre */
+                let half = (word.len() / 2) as i32;
+                let mut cells: Vec<(usize, usize)> = dirs_matched.iter()
+                    .flat_map(|dir| {
+                        let (sx, sy) = (x as i32 - half * dir.0, y as i32 - half * dir.1);
+                        WordMatch { x: sx as usize, y: sy as usize, dir: *dir, len: word.len() }.cells(input)
+                    })
+                    .collect();
+                cells.sort();
+                cells.dedup();
+
+                placements.push(Placement { cells });
             }
         }
     }
 
-    count
+    placements
 }
 
 // Search for `word` within `input` at the given rotation (`dir`) around `x`,`y`. With the
 //  center of `word` used as the rotation point.
-fn find_word2( input: &Vec<String>, word: &str, x: usize, y: usize, dir: &(i32, i32) ) -> bool {
+fn find_word2( input: &Grid, word: &[char], x: usize, y: usize, dir: &(i32, i32) ) -> bool {
     let mut matches = true;
 
     let x = x as i32;
@@ -125,20 +327,13 @@ fn find_word2( input: &Vec<String>, word: &str, x: usize, y: usize, dir: &(i32,
     let x  = x - (half * dir.0);
     let y = y - (half * dir.1);
 
-    for i in 0..word.len() {
+    for (i, &c) in word.iter().enumerate() {
 
         let dx = i as i32 * dir.0;
         let dy = i as i32 * dir.1;
 
-        /* Is index within input? */
-        if let Some((nx, ny)) = is_inside(&input, x + dx, y + dy) {
-            /* Check char for match */
-            if input[ny].as_bytes()[nx] != word.as_bytes()[i] {
-                matches = false;
-                break;
-            }
-        }
-        else {
+        /* Check char for match, off the grid never matches */
+        if input.get(x + dx, y + dy, false) != Some(c) {
             matches = false;
             break;
         }
@@ -147,15 +342,28 @@ fn find_word2( input: &Vec<String>, word: &str, x: usize, y: usize, dir: &(i32,
     matches
 }
 
-fn is_inside( input: &Vec<String>, x: i32, y: i32 ) -> Option<(usize, usize)> {
-    let height = input.len() as i32;
-    let width = input[0].len() as i32;
-
-    if x >= 0 && x < width && y >= 0 && y < height {
-        Some((x as usize, y as usize))
-    } else {
-        None
+/// Find placements of `kernel` under every rotation and reflection.
+///
+/// Orientations that coincide (for symmetric kernels) are only tried once,
+/// and placements are told apart by the cells they cover, so no placement is
+/// counted twice.
+fn find_kernel( input: &Grid, kernel: &Kernel) -> Vec<Placement> {
+    let mut placements = HashSet::new();
+
+    for orientation in kernel.orientations() {
+        for y in 0..input.height() {
+            for x in 0..input.width() {
+                if let Some(mut cells) = orientation.match_at(input, x as i32, y as i32) {
+                    cells.sort();
+                    placements.insert(Placement { cells });
+                }
+            }
+        }
     }
+
+    let mut placements: Vec<Placement> = placements.into_iter().collect();
+    placements.sort_by(|a, b| a.cells.cmp(&b.cells));
+    placements
 }
 
 
@@ -163,32 +371,185 @@ fn is_inside( input: &Vec<String>, x: i32, y: i32 ) -> Option<(usize, usize)> {
 mod test {
     use super::*;
 
+    fn grid(rows: &[&str]) -> Grid {
+        Grid::new(rows).unwrap()
+    }
+
     #[test]
     fn example_input() {
-        let input = vec![
-            String::from(".M.S......"),
-            String::from("..A..MSMS."),
-            String::from(".M.S.MAA.."),
-            String::from("..A.ASMSM."),
-            String::from(".M.S.M...."),
-            String::from(".........."),
-            String::from("S.S.S.S.S."),
-            String::from(".A.A.A.A.."),
-            String::from("M.M.M.M.M."),
-            String::from(".........."),
-        ];
-
-        assert_eq!(9, count_matches2(&input, "MAS"));
+        let input = grid(&[
+            ".M.S......",
+            "..A..MSMS.",
+            ".M.S.MAA..",
+            "..A.ASMSM.",
+            ".M.S.M....",
+            "..........",
+            "S.S.S.S.S.",
+            ".A.A.A.A..",
+            "M.M.M.M.M.",
+            "..........",
+        ]);
+
+        assert_eq!(9, find_matches2(&input, "MAS").len());
+    }
+
+    #[test]
+    fn word_list() {
+        let input = grid(&[
+            "MMMSXXMASM",
+            "MSAMXMSMSA",
+            "AMXSXMAAMM",
+            "MSAMASMSMX",
+            "XMASAMXAMM",
+            "XXAMMXXAMA",
+            "SMSMSASXSS",
+            "SAXAMASAAA",
+            "MAMMMXMMMM",
+            "MXMXAXMASX",
+        ]);
+
+        let words = ["XMAS", "MAS", "SAM", "AXA", "XM", "QQ", "XMASX"];
+        let expected: Vec<u32> = words.iter().map(|w| find_matches(&input, w, &Search::default()).len() as u32).collect();
+
+        assert_eq!(18, expected[0]);
+        assert_eq!(expected, count_words(&input, &words, &Search::default()));
+    }
+
+    #[test]
+    fn kernels() {
+        let input = grid(&[
+            ".M.S......",
+            "..A..MSMS.",
+            ".M.S.MAA..",
+            "..A.ASMSM.",
+            ".M.S.M....",
+            "..........",
+            "S.S.S.S.S.",
+            ".A.A.A.A..",
+            "M.M.M.M.M.",
+            "..........",
+        ]);
+
+        let x_mas = Kernel::parse("M.S/.A./M.S").unwrap();
+        assert_eq!(4, x_mas.orientations().len());
+        assert_eq!(find_matches2(&input, "MAS").len(), find_kernel(&input, &x_mas).len());
+
+        /* A straight word is a 1 row kernel, only turned in 90 degree steps.
+         * Palindromes match their own reflection, but only count once. */
+        assert_eq!(4, Kernel::parse("XMAS").unwrap().orientations().len());
+        let sas = grid(&["SAS", "AAA", "SAS"]);
+        assert_eq!(4, find_kernel(&sas, &Kernel::parse("SAS").unwrap()).len());
+
+        /* Plus shape */
+        let plus = Kernel::parse(".M./MAS/.S.").unwrap();
+        let cross = grid(&[".M.", "MAS", ".S."]);
+        assert_eq!(1, find_kernel(&cross, &plus).len());
+
+        assert!(Kernel::parse("M.S/.A").is_err());
+        assert!(Kernel::parse("../..").is_err());
+    }
+
+    #[test]
+    fn render_matches() {
+        let input = grid(&[
+            "..X...",
+            ".SAMX.",
+            ".A..A.",
+            "XMAS.S",
+            ".X....",
+        ]);
+
+        let matches = find_matches(&input, "XMAS", &Search::default());
+        assert_eq!(vec![
+            WordMatch { x: 2, y: 0, dir: (1, 1), len: 4 },
+            WordMatch { x: 4, y: 1, dir: (-1, 0), len: 4 },
+            WordMatch { x: 0, y: 3, dir: (1, 0), len: 4 },
+            WordMatch { x: 1, y: 4, dir: (0, -1), len: 4 },
+        ], matches);
+
+        let cells: Vec<Vec<(usize, usize)>> = matches.iter().map(|m| m.cells(&input)).collect();
+        let expected = "\
+..X...
+.SAMX.
+.A..A.
+XMAS.S
+.X....
+";
+        assert_eq!(expected, render::render(&input, &cells, false));
+
+        /* Only the cells of the one match survive, in the first colour */
+        let line = grid(&["XMASX"]);
+        let cells = vec![find_matches(&line, "XMAS", &Search::default())[0].cells(&line)];
+        assert_eq!("\x1b[31mX\x1b[0m\x1b[31mM\x1b[0m\x1b[31mA\x1b[0m\x1b[31mS\x1b[0m.\n", render::render(&line, &cells, true));
+
+        let x = find_matches2(&grid(&["M.S", ".A.", "M.S"]), "MAS");
+        assert_eq!(vec![Placement { cells: vec![(0, 0), (0, 2), (1, 1), (2, 0), (2, 2)] }], x);
+    }
+
+    #[test]
+    fn unicode_grid() {
+        /* Multi-byte letters take one cell each */
+        let input = grid(&[
+            "ÉTÉ..",
+            "Ж.Т..",
+            "ЖИТЬ.",
+            "..Ь..",
+        ]);
+        assert_eq!(5, input.width());
+
+        assert_eq!(2, find_matches(&input, "ÉTÉ", &Search::default()).len());
+        assert_eq!(1, find_matches(&input, "ЖИТЬ", &Search::default()).len());
+        assert_eq!(vec![2, 1, 2], count_words(&input, &["ÉTÉ", "ТТЬ", "ЖЖ"], &Search::default()));
+        assert_eq!(1, find_kernel(&input, &Kernel::parse("Т/Т/Ь").unwrap()).len());
+        assert_eq!("ÉTÉ..\n.....\n.....\n.....\n", render::render(&input, &[find_matches(&input, "ÉTÉ", &Search::default())[0].cells(&input)], false));
+    }
+
+    #[test]
+    fn search_modes() {
+        let wrapped = grid(&["ASXM", "...."]);
+        let wrap = Search { wrap: true, ..Search::default() };
+        assert_eq!(0, find_matches(&wrapped, "XMAS", &Search::default()).len());
+        assert_eq!(vec![WordMatch { x: 2, y: 0, dir: (1, 0), len: 4 }], find_matches(&wrapped, "XMAS", &wrap));
+        assert_eq!(vec![(2, 0), (3, 0), (0, 0), (1, 0)], find_matches(&wrapped, "XMAS", &wrap)[0].cells(&wrapped));
+        assert_eq!(vec![1], count_words(&wrapped, &["XMAS"], &wrap));
+
+        let knight = grid(&["X....", "..M..", "....A"]);
+        let knights = Search { steps: KNIGHT_MOVES.to_vec(), ..Search::default() };
+        assert_eq!(0, find_matches(&knight, "XMA", &Search::default()).len());
+        assert_eq!(1, find_matches(&knight, "XMA", &knights).len());
+        assert_eq!(vec![1, 1], count_words(&knight, &["XMA", "AMX"], &knights));
+
+        assert_eq!((2, -1), parse_step("2,-1").unwrap());
+        assert!(parse_step("0,0").is_err());
+        assert!(parse_step("2").is_err());
+
+        /* Overlap is only checked against matches of the same word */
+        let line = grid(&["AAAA"]);
+        let disjoint = Search { overlapping: false, ..Search::default() };
+        assert_eq!(6, find_matches(&line, "AA", &Search::default()).len());
+        assert_eq!(2, find_matches(&line, "AA", &disjoint).len());
+        assert_eq!(vec![6, 32], count_words(&line, &["AA", "A"], &Search::default()));
+        assert_eq!(vec![2, 4], count_words(&line, &["AA", "A"], &disjoint));
+    }
+
+    #[test]
+    fn ragged_grid() {
+        let err = Grid::new(&["ABC", "AB", "ABC"]).unwrap_err();
+        assert_eq!("Row 2 is 2 letters wide, expected 3", err.to_string());
+
+        /* Width is in letters, not bytes */
+        assert!(Grid::new(&["ÉÉ", "EE"]).is_ok());
+        assert!(Grid::new(&Vec::<String>::new()).is_ok());
     }
 
     #[test]
     fn simple_input() {
-        let input = vec![
-            String::from("M.S"),
-            String::from(".A."),
-            String::from("M.S"),
-        ];
+        let input = grid(&[
+            "M.S",
+            ".A.",
+            "M.S",
+        ]);
 
-        assert_eq!(1, count_matches2(&input, "MAS"));
+        assert_eq!(1, find_matches2(&input, "MAS").len());
     }
 }
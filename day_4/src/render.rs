@@ -0,0 +1,37 @@
+//! Draw search results over the grid, like the puzzle description does.
+
+use crate::grid::Grid;
+
+/// ANSI foreground colours cycled through, one per match.
+const COLOURS: [u8; 6] = [31, 32, 33, 34, 35, 36];
+
+/// Reproduce `input` with every letter not covered by one of `matches`
+/// replaced by `.`.
+///
+/// With `colour`, each match's letters are drawn in their own ANSI colour.
+/// A letter shared by several matches takes the colour of the first.
+pub fn render(input: &Grid, matches: &[Vec<(usize, usize)>], colour: bool) -> String {
+    let mut owner = vec![vec![None; input.width()]; input.height()];
+
+    for (i, cells) in matches.iter().enumerate() {
+        for &(x, y) in cells {
+            owner[y][x].get_or_insert(i);
+        }
+    }
+
+    let mut out = String::new();
+    for (y, owners) in owner.iter().enumerate() {
+        for (x, owner) in owners.iter().enumerate() {
+            match owner {
+                None => out.push('.'),
+                Some(i) if colour => {
+                    out += &format!("\x1b[{}m{}\x1b[0m", COLOURS[i % COLOURS.len()], input.at(x, y));
+                }
+                Some(_) => out.push(input.at(x, y)),
+            }
+        }
+        out.push('\n');
+    }
+
+    out
+}
@@ -0,0 +1,52 @@
+//! Prefix tree used to search for many words at once.
+
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct Node {
+    children: HashMap<char, usize>,
+    /// Indices (into the word list) of the words ending at this node.
+    words: Vec<usize>,
+}
+
+pub struct Trie {
+    nodes: Vec<Node>,
+}
+
+impl Trie {
+    pub fn new(words: &[&str]) -> Self {
+        let mut trie = Trie { nodes: vec![Node::default()] };
+
+        for (i, word) in words.iter().enumerate() {
+            let mut node = 0;
+            for c in word.chars() {
+                node = match trie.nodes[node].children.get(&c) {
+                    Some(&next) => next,
+                    None => {
+                        trie.nodes.push(Node::default());
+                        let next = trie.nodes.len() - 1;
+                        trie.nodes[node].children.insert(c, next);
+                        next
+                    }
+                };
+            }
+            trie.nodes[node].words.push(i);
+        }
+
+        trie
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    /// Follow the edge for `c` out of `node`, if there is one.
+    pub fn step(&self, node: usize, c: char) -> Option<usize> {
+        self.nodes[node].children.get(&c).copied()
+    }
+
+    /// Words ending at `node`.
+    pub fn words(&self, node: usize) -> &[usize] {
+        &self.nodes[node].words
+    }
+}
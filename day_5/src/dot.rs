@@ -0,0 +1,21 @@
+//! Graphviz export of the page ordering rules.
+//!
+//! Nodes are pages and an edge runs from each page to the pages it must come
+//! before. Edges for rules a print list breaks are drawn in red.
+
+use std::collections::HashSet;
+
+use petgraph::{dot::{Config, Dot}, prelude::*};
+
+/// `graph` in DOT, with the `violated` (before, after) edges highlighted.
+pub fn to_dot(graph: &DiGraphMap<u32, ()>, violated: &HashSet<(u32, u32)>) -> String {
+    let edge_attrs = |_, (before, after, _): (u32, u32, &())| {
+        if violated.contains(&(before, after)) {
+            "color = red penwidth = 2 ".to_string()
+        } else {
+            String::new()
+        }
+    };
+
+    format!("{:?}", Dot::with_attr_getters(graph, &[Config::EdgeNoLabel], &edge_attrs, &|_, _| String::new()))
+}
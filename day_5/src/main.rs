@@ -1,5 +1,14 @@
-use std::{collections::{HashMap, HashSet}, error, fs::File, io::{self, BufRead}, cmp::*};
+use std::{collections::HashSet, error, fmt, fs::File, io::{self, BufRead, Write}};
 
+pub use rules::Rules;
+use strategy::{GlobalToposort, OrderingStrategy, PerList};
+
+mod dot;
+mod ordering;
+mod queue;
+mod reorder;
+mod rules;
+mod strategy;
 
 // This lets us bubble up all errors to main() regardless of type
 type Error = Box<dyn error::Error>;
@@ -8,6 +17,24 @@ pub type Result<T> = std::result::Result<T, Error>;
 // const INPUT: &str = "data/day5_1_example.txt";
 const INPUT: &str = "data/day5_1.txt";
 
+/// A rule broken by a print list: `before` must be printed before `after`,
+/// but comes later. Positions are indices into the list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Violation {
+    before: u32,
+    after: u32,
+    before_pos: usize,
+    after_pos: usize,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        /* Positions are shown 1-based */
+        write!(f, "{}|{}: {} is page {} of the list but {} is page {}",
+            self.before, self.after, self.before, self.before_pos + 1, self.after, self.after_pos + 1)
+    }
+}
+
 fn main() -> Result<()> {
     let cwd = std::env::current_dir()?;
     // println!("The current directory is {}", cwd.display());
@@ -17,26 +44,144 @@ fn main() -> Result<()> {
 
     let (pg2subs, print_lists) = parse_input(&in_file)?;
 
+    /* `--explain` lists every rule broken by each incorrect print list.
+     * `--min-moves` fixes lists with the fewest page moves, and with
+     * `--explain` lists the moves.
+     * `--dot all|<n>` prints the full rule graph, or the rules between the
+     * pages of print list n (1-based), as DOT instead.
+     * `--orders <cap>` counts the valid orders of each incorrect list and
+     * lists up to cap of them.
+     * `--interactive` reads commands from stdin to change the rules and
+     * re-check the lists, see `run_command`.
+     * `--strategy per-list|global` picks how lists are checked and how the
+     * default fix orders them, see `strategy`. Nothing else uses it:
+     * `--explain`, `--min-moves`, `--orders`, the ambiguity check and
+     * `--interactive` always go by the rules between each list's own pages. */
+    let mut explain = false;
+    let mut min_moves = false;
+    let mut dot = None;
+    let mut orders_cap = None;
+    let mut interactive = false;
+    let mut strategy_name = "per-list".to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--explain" => explain = true,
+            "--min-moves" => min_moves = true,
+            "--dot" => dot = Some(args.next().ok_or("--dot requires `all` or a print list number")?),
+            "--interactive" => interactive = true,
+            "--strategy" => strategy_name = args.next().ok_or("--strategy requires per-list or global (it only affects validation and the default fix)")?,
+            "--orders" => orders_cap = Some(args.next().ok_or("--orders requires a cap")?.parse::<usize>()?),
+            _ => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
+    if let Some(which) = dot {
+        print!("{}", export_dot(&which, &print_lists, &pg2subs)?);
+        return Ok(());
+    }
+
+    if interactive {
+        let mut queue = queue::PrintQueue::new(pg2subs, print_lists);
+        let stdin = io::stdin();
+
+        print!("> ");
+        io::stdout().flush()?;
+        for line in stdin.lock().lines() {
+            let line = line?;
+            if matches!(line.trim(), "quit" | "exit") {
+                break;
+            }
+
+            match run_command(&mut queue, &line) {
+                Ok(out) => print!("{}", out),
+                Err(e) => println!("Error: {}", e),
+            }
+            print!("> ");
+            io::stdout().flush()?;
+        }
+        return Ok(());
+    }
+
+    let strategy = make_strategy(&strategy_name, &pg2subs)?;
+
     /* Find correct print lists */
     let (correct_lists, incorrect_lists) =
-        validate_print_lists(print_lists, &pg2subs);
+        validate_print_lists(print_lists, strategy.as_ref());
 
     let mid_sum = sum_mids(&correct_lists);
 
     println!("1. Total: {}, Valid: {}, Sum: {}", correct_lists.len() + incorrect_lists.len(), correct_lists.len(), mid_sum);
 
+    if explain {
+        for list in &incorrect_lists {
+            println!("{:?}", list);
+            let violations = validate_print_list(list, &pg2subs);
+            if violations.is_empty() {
+                println!("    breaks no rule between its own pages, only the {} order", strategy_name);
+            }
+            for violation in violations {
+                println!("    {}", violation);
+            }
+        }
+    }
+
+    if let Some(cap) = orders_cap {
+        for list in &incorrect_lists {
+            println!("{:?}: {} valid orders", list, ordering::count_orders(list, &pg2subs)?);
+            for order in ordering::orders(list, &pg2subs, cap)? {
+                println!("    {:?}", order);
+            }
+        }
+    }
+
     /* Fix incorrect lists */
-    let corrected_lists =
-        fix_lists(incorrect_lists, &pg2subs);
+    let corrected_lists = if min_moves {
+        let mut lists = Vec::new();
+        let mut total_moves = 0;
+
+        for list in &incorrect_lists {
+            let fixed = reorder::reorder(list, &pg2subs)
+                .map_err(|e| format!("Can't order {:?}: {}", list, e))?;
+
+            if explain {
+                println!("{:?} -> {:?}", list, fixed.order);
+                for step in &fixed.moves {
+                    println!("    {}", step);
+                }
+            }
+
+            total_moves += fixed.moves.len();
+            lists.push(fixed.order);
+        }
+
+        println!("Moves: {}", total_moves);
+        lists
+    } else {
+        fix_lists(incorrect_lists, strategy.as_ref())?
+    };
 
     let mid_sum = sum_mids(&corrected_lists);
 
     println!("2. Sum: {}", mid_sum);
 
+    /* The puzzle assumes each list has one valid order, check the middle
+     * page at least can't differ */
+    let (min_sum, max_sum) = sum_mids_range(&corrected_lists, &pg2subs)?;
+    if min_sum != max_sum {
+        for list in &corrected_lists {
+            let mids = ordering::possible_mids(list, &pg2subs)?;
+            if mids.len() > 1 {
+                println!("Ambiguous: {:?} can have any of {:?} in the middle", list, mids);
+            }
+        }
+        println!("2. Sum is ambiguous, anywhere from {} to {}", min_sum, max_sum);
+    }
+
     Ok(())
 }
 
-fn sum_mids(lists: &Vec<Vec<u32>>) -> u32 {
+fn sum_mids(lists: &[Vec<u32>]) -> u32 {
     let mut sum = 0;
     for list in lists {
         sum += list[list.len()/2];
@@ -45,12 +190,34 @@ fn sum_mids(lists: &Vec<Vec<u32>>) -> u32 {
     sum
 }
 
-fn validate_print_lists(print_lists: Vec<Vec<u32>>, pg2subs: &HashMap<u32, HashSet<u32>>) -> (Vec<Vec<u32>>, Vec<Vec<u32>>) {
+/// Smallest and largest `sum_mids` over every valid order of each of
+/// `lists`. They differ if any list's middle page isn't fixed by the rules.
+fn sum_mids_range(lists: &[Vec<u32>], pg2subs: &Rules) -> Result<(u32, u32)> {
+    let mut range = (0, 0);
+    for list in lists {
+        let mids = ordering::possible_mids(list, pg2subs)?;
+        range.0 += mids.iter().min().unwrap_or(&0);
+        range.1 += mids.iter().max().unwrap_or(&0);
+    }
+
+    Ok(range)
+}
+
+/// The ordering strategy called `name`.
+fn make_strategy<'a>(name: &str, pg2subs: &'a Rules) -> Result<Box<dyn OrderingStrategy + 'a>> {
+    match name {
+        "per-list" => Ok(Box::new(PerList::new(pg2subs))),
+        "global" => Ok(Box::new(GlobalToposort::new(pg2subs).map_err(|e| format!("Can't sort all the rules: {}", e))?)),
+        _ => Err(format!("Unknown strategy: {}, expected per-list or global", name).into()),
+    }
+}
+
+fn validate_print_lists(print_lists: Vec<Vec<u32>>, strategy: &dyn OrderingStrategy) -> (Vec<Vec<u32>>, Vec<Vec<u32>>) {
     let mut correct_lists = Vec::new();
     let mut incorrect_lists = Vec::new();
 
     for print_list in print_lists {
-        if validate_print_list(&print_list, &pg2subs) {
+        if strategy.is_valid(&print_list) {
 
             correct_lists.push(print_list);
         } else {
@@ -61,55 +228,137 @@ fn validate_print_lists(print_lists: Vec<Vec<u32>>, pg2subs: &HashMap<u32, HashS
     (correct_lists, incorrect_lists)
 }
 
-fn validate_print_list(print_list: &Vec<u32>, pg2subs: &HashMap<u32, HashSet<u32>>) -> bool {
+/// Every rule `print_list` breaks, empty if it's in a valid order. Ordered
+/// by the position of the page that should have come first.
+fn validate_print_list(print_list: &[u32], pg2subs: &Rules) -> Vec<Violation> {
+    let mut violations = Vec::new();
 
     for (i, pg_num) in print_list.iter().enumerate() {
         let subs =
-        match pg2subs.get(pg_num) {
+        match pg2subs.after(pg_num) {
             Some(subs) => subs,
             None => continue,
         };
 
         /* Verify order */
-        for idx in 0..i {
+        for (idx, sub) in print_list[..i].iter().enumerate() {
             /* Sub appears in print list before current page */
-            if subs.contains(&print_list[idx]) {
-                return false;
+            if subs.contains(sub) {
+                violations.push(Violation { before: *pg_num, after: *sub, before_pos: i, after_pos: idx });
             }
         }
     }
 
-    true
+    violations
 }
 
-fn fix_lists(mut bad_lists: Vec<Vec<u32>>, pg2subs: &HashMap<u32, HashSet<u32>>) -> Vec<Vec<u32>> {
+/// DOT for `which`: `all` for the full rule graph with every rule any list
+/// breaks highlighted, or a 1-based print list number for the rules between
+/// that list's pages with the ones it breaks highlighted.
+fn export_dot(which: &str, print_lists: &[Vec<u32>], pg2subs: &Rules) -> Result<String> {
+    let broken = |list: &[u32]| -> Vec<(u32, u32)> {
+        validate_print_list(list, pg2subs).iter().map(|v| (v.before, v.after)).collect()
+    };
+
+    if which == "all" {
+        let violated = print_lists.iter().flat_map(|list| broken(list)).collect();
+        return Ok(dot::to_dot(&ordering::graph(pg2subs), &violated));
+    }
+
+    let list = which.parse::<usize>().ok()
+        .and_then(|n| print_lists.get(n.checked_sub(1)?))
+        .ok_or_else(|| format!("No print list {}, expected `all` or 1 to {}", which, print_lists.len()))?;
+
+    Ok(dot::to_dot(&ordering::subgraph(list, pg2subs), &broken(list).into_iter().collect()))
+}
+
+/// Put each of `bad_lists` in a valid order. Fails if the strategy can't
+/// order a list.
+fn fix_lists(mut bad_lists: Vec<Vec<u32>>, strategy: &dyn OrderingStrategy) -> Result<Vec<Vec<u32>>> {
     for bad_list in &mut bad_lists {
-        bad_list.sort_by(|l: &u32, r| {
-            /* l < r: l must come before r */
-            if let Some(subs) = pg2subs.get(l) {
-                if subs.contains(r) {
-                    return Ordering::Less
-                }
-            }
+        *bad_list = strategy.order(bad_list)
+            .map_err(|e| format!("Can't order {:?}: {}", bad_list, e))?;
+    }
 
-            /* l > r: l must come after r */
-            if let Some(subs) = pg2subs.get(r) {
-                if subs.contains(l) {
-                    return Ordering::Greater
+    Ok(bad_lists)
+}
+
+
+/// Run one interactive command against `queue`, returning what to print.
+///
+/// * `add X|Y`, `remove X|Y`: change a rule, reporting lists it made valid
+///   or invalid.
+/// * `before X`, `after X`: pages that must come before or after page X.
+/// * `check N`: whether print list N (1-based) is valid, and why not.
+/// * `sum`: both puzzle answers under the current rules.
+fn run_command(queue: &mut queue::PrintQueue, line: &str) -> Result<String> {
+    let mut out = String::new();
+    let (command, arg) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+    let arg = arg.trim();
+
+    let page_list = |pages: Option<&HashSet<u32>>| {
+        let mut pages: Vec<u32> = pages.into_iter().flatten().copied().collect();
+        pages.sort();
+        format!("{:?}\n", pages)
+    };
+
+    match command {
+        "add" | "remove" => {
+            let (before, after) = parse_rule(arg)?;
+            let result = match command {
+                "add" => queue.add_rule(before, after).ok_or_else(|| format!("{}|{} is already a rule", before, after))?,
+                _ => queue.remove_rule(before, after).ok_or_else(|| format!("{}|{} isn't a rule", before, after))?,
+            };
+
+            out += &format!("Checked {} lists\n", result.checked);
+            for i in result.changed {
+                let state = if queue.is_valid(i) { "valid" } else { "invalid" };
+                out += &format!("List {} is now {}: {:?}\n", i + 1, state, queue.lists()[i]);
+            }
+        }
+        "before" => out += &page_list(queue.rules().before(&arg.parse()?)),
+        "after" => out += &page_list(queue.rules().after(&arg.parse()?)),
+        "check" => {
+            let i = arg.parse::<usize>().ok()
+                .and_then(|n| n.checked_sub(1))
+                .filter(|&i| i < queue.lists().len())
+                .ok_or_else(|| format!("No print list {}, expected 1 to {}", arg, queue.lists().len()))?;
+
+            if queue.is_valid(i) {
+                out += &format!("{:?} is valid\n", queue.lists()[i]);
+            } else {
+                out += &format!("{:?} is invalid\n", queue.lists()[i]);
+                for violation in queue.violations(i) {
+                    out += &format!("    {}\n", violation);
                 }
             }
-
-            /* r isn't a sub of l nor is l a sub of r. */
-            Ordering::Equal
-        });
+        }
+        "sum" => {
+            let (correct, incorrect): (Vec<Vec<u32>>, Vec<Vec<u32>>) = (0..queue.lists().len())
+                .map(|i| (queue.is_valid(i), queue.lists()[i].clone()))
+                .fold((Vec::new(), Vec::new()), |(mut correct, mut incorrect), (valid, list)| {
+                    if valid { correct.push(list) } else { incorrect.push(list) }
+                    (correct, incorrect)
+                });
+
+            out += &format!("1. Valid: {}, Sum: {}\n", correct.len(), sum_mids(&correct));
+            out += &format!("2. Sum: {}\n", sum_mids(&fix_lists(incorrect, &PerList::new(queue.rules()))?));
+        }
+        "help" => out += "Commands: add X|Y, remove X|Y, before X, after X, check N, sum, quit\n",
+        _ => return Err(format!("Unknown command: {}", line.trim()).into()),
     }
 
-    bad_lists
+    Ok(out)
 }
 
+/// Parse an `X|Y` rule into (X, Y).
+fn parse_rule(s: &str) -> Result<(u32, u32)> {
+    let (high_pg, low_pg) = s.split_once('|').ok_or_else(|| format!("Rule must be X|Y: {}", s))?;
+    Ok((high_pg.trim().parse()?, low_pg.trim().parse()?))
+}
 
-fn parse_input(f: &File) -> Result<(HashMap<u32, HashSet<u32>>,Vec<Vec<u32>>)> {
-    let mut relations: HashMap<u32, HashSet<u32>> = HashMap::new();
+fn parse_input(f: &File) -> Result<(Rules, Vec<Vec<u32>>)> {
+    let mut relations = Rules::new();
     let mut print_lists: Vec<Vec<u32>> = Vec::new();
     let reader = io::BufReader::new(f);
     let mut parsing_first = true;
@@ -123,16 +372,8 @@ fn parse_input(f: &File) -> Result<(HashMap<u32, HashSet<u32>>,Vec<Vec<u32>>)> {
 
         /* Parsing relations */
         if parsing_first {
-            let tokens: Vec<&str> = line.split("|").filter(|k| !k.is_empty()).collect();
-            assert_eq!(tokens.len(), 2);
-
-            let high_pg: u32 = tokens[0].parse()?;
-            let low_pg: u32 = tokens[1].parse()?;
-
-            let low_pages =
-                relations.entry(high_pg).or_insert_with(|| HashSet::new());
-
-            low_pages.insert(low_pg);
+            let (high_pg, low_pg) = parse_rule(&line)?;
+            relations.add(high_pg, low_pg);
         }
         /* Parsing print lists */
         else {
@@ -152,11 +393,287 @@ fn parse_input(f: &File) -> Result<(HashMap<u32, HashSet<u32>>,Vec<Vec<u32>>)> {
 
 #[cfg(test)]
 mod test {
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
     use super::*;
+    use ordering::Cycle;
+
+    const EXAMPLE_RULES: [(u32, u32); 21] = [
+        (47, 53), (97, 13), (97, 61), (97, 47), (75, 29), (61, 13), (75, 53), (29, 13), (97, 29), (53, 29), (61, 53),
+        (97, 53), (61, 29), (47, 13), (75, 47), (97, 75), (47, 61), (75, 61), (47, 29), (75, 13), (53, 13),
+    ];
+
+    fn rules(pairs: &[(u32, u32)]) -> Rules {
+        pairs.iter().copied().collect()
+    }
+
+    fn example_lists() -> Vec<Vec<u32>> {
+        vec![
+            vec![75, 47, 61, 53, 29],
+            vec![97, 61, 53, 29, 13],
+            vec![75, 29, 13],
+            vec![75, 97, 47, 61, 53],
+            vec![61, 13, 29],
+            vec![97, 13, 75, 29, 47],
+        ]
+    }
+
+    fn cycle_of(list: &[u32], rules: &Rules) -> Cycle {
+        let err = ordering::order(list, rules).unwrap_err();
+        err.downcast_ref::<Cycle>().expect("not a cycle").clone()
+    }
 
     #[test]
-    fn simple() {
+    fn example() {
+        let rules = rules(&EXAMPLE_RULES);
+        let (correct, incorrect) = validate_print_lists(example_lists(), &PerList::new(&rules));
+        assert_eq!(143, sum_mids(&correct));
+
+        let fixed = fix_lists(incorrect, &PerList::new(&rules)).unwrap();
+        assert_eq!(vec![vec![97, 75, 47, 61, 53], vec![61, 29, 13], vec![97, 75, 47, 29, 13]], fixed);
+        assert_eq!(123, sum_mids(&fixed));
+    }
 
+    #[test]
+    fn violations() {
+        let rules = rules(&EXAMPLE_RULES);
+        assert!(validate_print_list(&[75, 47, 61, 53, 29], &rules).is_empty());
 
+        assert_eq!(vec![Violation { before: 97, after: 75, before_pos: 1, after_pos: 0 }],
+            validate_print_list(&[75, 97, 47, 61, 53], &rules));
+
+        let found: Vec<(u32, u32)> = validate_print_list(&[97, 13, 75, 29, 47], &rules).iter()
+            .map(|v| (v.before, v.after))
+            .collect();
+        assert_eq!(vec![(75, 13), (29, 13), (47, 13), (47, 29)], found);
+
+        assert_eq!("29|13: 29 is page 3 of the list but 13 is page 2",
+            validate_print_list(&[61, 13, 29], &rules)[0].to_string());
+    }
+
+    /// Apply `moves` to `list`.
+    fn replay(list: &[u32], moves: &[reorder::Move]) -> Vec<u32> {
+        let mut list = list.to_vec();
+        for step in moves {
+            assert_eq!(step.page, list.remove(step.from));
+            list.insert(step.to, step.page);
+        }
+        list
+    }
+
+    /// Fewest single page moves making `list` valid, by breadth first search.
+    fn fewest_moves(list: &[u32], rules: &Rules) -> usize {
+        let mut seen = HashSet::from([list.to_vec()]);
+        let mut queue = std::collections::VecDeque::from([(list.to_vec(), 0)]);
+
+        while let Some((list, depth)) = queue.pop_front() {
+            if validate_print_list(&list, rules).is_empty() {
+                return depth;
+            }
+            for from in 0..list.len() {
+                for to in 0..list.len() {
+                    let mut next = list.clone();
+                    let page = next.remove(from);
+                    next.insert(to, page);
+                    if seen.insert(next.clone()) {
+                        queue.push_back((next, depth + 1));
+                    }
+                }
+            }
+        }
+        unreachable!("acyclic rules always have a valid order")
+    }
+
+    #[test]
+    fn min_moves() {
+        let rules = rules(&EXAMPLE_RULES);
+
+        let fixed = reorder::reorder(&[75, 97, 47, 61, 53], &rules).unwrap();
+        assert_eq!(vec![97, 75, 47, 61, 53], fixed.order);
+        /* Moving 75 back is as good as moving 97 forward */
+        assert_eq!(vec![reorder::Move { page: 75, from: 0, to: 1 }], fixed.moves);
+        assert_eq!("move 75 from page 1 to page 2", fixed.moves[0].to_string());
+
+        let fixed = reorder::reorder(&[97, 13, 75, 29, 47], &rules).unwrap();
+        assert_eq!(vec![97, 75, 47, 29, 13], fixed.order);
+        assert_eq!(2, fixed.moves.len());
+        assert_eq!(fixed.order, replay(&[97, 13, 75, 29, 47], &fixed.moves));
+
+        assert!(reorder::reorder(&[75, 47, 61, 53, 29], &rules).unwrap().moves.is_empty());
+
+        let mut rng = StdRng::seed_from_u64(2024);
+
+        for _ in 0..300 {
+            /* Rules only run from lower to higher pages, so there are no cycles */
+            let len = rng.gen_range(1..=6);
+            let pairs: Vec<(u32, u32)> = (0..rng.gen_range(0..12))
+                .map(|_| (rng.gen_range(0..8), rng.gen_range(0..8)))
+                .filter(|(a, b)| a < b)
+                .collect();
+            let rules = self::rules(&pairs);
+
+            let mut list: Vec<u32> = (0..8).collect();
+            list.shuffle(&mut rng);
+            list.truncate(len);
+
+            let fixed = reorder::reorder(&list, &rules).unwrap();
+            assert!(validate_print_list(&fixed.order, &rules).is_empty(), "{:?} {:?}", list, pairs);
+            assert_eq!(fixed.order, replay(&list, &fixed.moves), "{:?} {:?}", list, pairs);
+            assert_eq!(fewest_moves(&list, &rules), fixed.moves.len(), "{:?} {:?}", list, pairs);
+        }
+    }
+
+    #[test]
+    fn ambiguous_orders() {
+        let example = rules(&EXAMPLE_RULES);
+        let (_, incorrect) = validate_print_lists(example_lists(), &PerList::new(&example));
+        for list in &incorrect {
+            assert_eq!(1, ordering::count_orders(list, &example).unwrap());
+        }
+        let fixed = fix_lists(incorrect, &PerList::new(&example)).unwrap();
+        assert_eq!((123, 123), sum_mids_range(&fixed, &example).unwrap());
+
+        /* 1 before 2 before 3, 4 and 5 anywhere */
+        let rules = rules(&[(1, 2), (2, 3)]);
+        assert_eq!(20, ordering::count_orders(&[3, 4, 2, 1, 5], &rules).unwrap());
+        assert_eq!(vec![2, 4, 1, 5], ordering::possible_mids(&[2, 4, 1, 5], &rules).unwrap());
+        assert_eq!(vec![3, 4, 2, 1, 5], ordering::possible_mids(&[3, 4, 2, 1, 5], &rules).unwrap());
+        let chain = self::rules(&[(0, 1), (1, 2), (2, 3), (3, 4), (4, 5)]);
+        assert_eq!(vec![6, 3, 2], ordering::possible_mids(&[5, 6, 4, 3, 2, 1, 0], &chain).unwrap());
+        assert_eq!((1, 5), sum_mids_range(&[vec![3, 4, 2, 1, 5]], &rules).unwrap());
+
+        let orders = ordering::orders(&[3, 2, 1, 4], &rules, 10).unwrap();
+        assert_eq!(vec![vec![1, 2, 3, 4], vec![1, 2, 4, 3], vec![1, 4, 2, 3], vec![4, 1, 2, 3]], orders);
+        assert_eq!(2, ordering::orders(&[3, 2, 1, 4], &rules, 2).unwrap().len());
+
+        /* Enumerating agrees with counting */
+        let list = [3, 6, 2, 1, 5, 4];
+        let rules = self::rules(&[(1, 2), (2, 3), (4, 5), (1, 5)]);
+        let count = ordering::count_orders(&list, &rules).unwrap();
+        let all = ordering::orders(&list, &rules, usize::MAX).unwrap();
+        assert_eq!(count as usize, all.len());
+        assert!(all.iter().all(|o| validate_print_list(o, &rules).is_empty()));
+        let mids: HashSet<u32> = all.iter().map(|o| o[3]).collect();
+        assert_eq!(mids, ordering::possible_mids(&list, &rules).unwrap().into_iter().collect());
+
+        assert!(ordering::count_orders(&[1, 2], &self::rules(&[(1, 2), (2, 1)])).is_err());
+    }
+
+    #[test]
+    fn rule_changes() {
+        let mut queue = queue::PrintQueue::new(rules(&EXAMPLE_RULES), example_lists());
+        assert_eq!("1. Valid: 3, Sum: 143\n2. Sum: 123\n", run_command(&mut queue, "sum").unwrap());
+        assert_eq!("[47, 75, 97]\n", run_command(&mut queue, "before 61").unwrap());
+        assert_eq!("[]\n", run_command(&mut queue, "after 13").unwrap());
+
+        /* 75,97,47,61,53 is only wrong because of 97|75 */
+        let change = queue.remove_rule(97, 75).unwrap();
+        assert_eq!(queue::Revalidation { checked: 2, changed: vec![3] }, change);
+        assert!(queue.is_valid(3));
+        assert!(queue.rules().before(&75).is_none());
+
+        /* Only lists with both pages are checked */
+        assert_eq!(Some(queue::Revalidation { checked: 0, changed: vec![] }), queue.add_rule(99, 13));
+        assert_eq!(Some(queue::Revalidation { checked: 2, changed: vec![] }), queue.add_rule(75, 97));
+        assert_eq!(None, queue.add_rule(75, 97));
+        assert!(run_command(&mut queue, "remove 13|99").is_err());
+        assert_eq!("Checked 3 lists\nList 1 is now invalid: [75, 47, 61, 53, 29]\nList 3 is now invalid: [75, 29, 13]\n", run_command(&mut queue, "add 29|75").unwrap());
+
+        assert!(run_command(&mut queue, "check 1").unwrap().contains("29|75: 29 is page 5 of the list but 75 is page 1"));
+        assert_eq!("[97, 61, 53, 29, 13] is valid\n", run_command(&mut queue, "check 2").unwrap());
+
+        /* A cycle doesn't end the session */
+        assert!(run_command(&mut queue, "sum").is_err());
+        run_command(&mut queue, "remove 29|75").unwrap();
+        assert!(run_command(&mut queue, "sum").is_ok());
+
+        assert!(run_command(&mut queue, "check 7").is_err());
+        assert!(run_command(&mut queue, "add 1-2").is_err());
+        assert!(run_command(&mut queue, "frobnicate").is_err());
+
+        /* A list repeating a page is still only checked once */
+        let mut repeats = queue::PrintQueue::new(rules(&[]), vec![vec![1, 2, 1, 2]]);
+        assert_eq!(Some(queue::Revalidation { checked: 1, changed: vec![0] }), repeats.add_rule(2, 1));
+    }
+
+    #[test]
+    fn dot_export() {
+        let rules = rules(&[(1, 2), (2, 3), (1, 3), (4, 5)]);
+        let lists = vec![vec![1, 3, 2], vec![5, 4]];
+
+        let list = export_dot("1", &lists, &rules).unwrap();
+        assert!(list.contains("0 [ label = \"1\" ]"), "{}", list);
+        assert!(list.contains("0 -> 1 [ ]"), "{}", list);
+        assert!(list.contains("2 -> 1 [ color = red penwidth = 2 ]"), "{}", list);
+        assert!(!list.contains("label = \"4\""), "{}", list);
+
+        let all = export_dot("all", &lists, &rules).unwrap();
+        assert_eq!(4, all.matches("->").count());
+        assert_eq!(2, all.matches("color = red").count());
+
+        assert!(export_dot("0", &lists, &rules).is_err());
+        assert!(export_dot("3", &lists, &rules).is_err());
+        assert!(export_dot("x", &lists, &rules).is_err());
+    }
+
+    #[test]
+    fn strategies() {
+        /* The example's full graph is acyclic, so both strategies agree */
+        let rules = rules(&EXAMPLE_RULES);
+        let global = make_strategy("global", &rules).unwrap();
+        let (correct, incorrect) = validate_print_lists(example_lists(), global.as_ref());
+        assert_eq!(143, sum_mids(&correct));
+        assert_eq!(123, sum_mids(&fix_lists(incorrect, global.as_ref()).unwrap()));
+
+        let pri_list = vec![97, 75, 47, 61, 53, 29, 13];
+        let global = GlobalToposort::new(&rules).unwrap();
+        assert_eq!(pri_list, global.order(&[13, 29, 53, 61, 47, 75, 97]).unwrap());
+        assert!(global.is_valid(&[75, 29, 13]));
+        assert!(!global.is_valid(&[61, 13, 29]));
+
+        /* Every list here can be ordered, but the full graph is a cycle */
+        let rules = self::rules(&[(1, 2), (2, 3), (3, 1), (4, 1)]);
+        let lists = vec![vec![1, 2], vec![3, 2], vec![4, 3, 1]];
+        let err = make_strategy("global", &rules).err().unwrap().to_string();
+        assert!(err.starts_with("Can't sort all the rules: rules "), "{}", err);
+
+        let per_list = make_strategy("per-list", &rules).unwrap();
+        let (correct, incorrect) = validate_print_lists(lists, per_list.as_ref());
+        assert_eq!(vec![vec![3, 2]], incorrect);
+        assert_eq!(vec![vec![2, 3]], fix_lists(incorrect, per_list.as_ref()).unwrap());
+        assert_eq!(vec![vec![1, 2], vec![4, 3, 1]], correct);
+
+        /* Unrelated pages are held to the global sort's arbitrary order */
+        let rules = self::rules(&[(1, 2), (3, 4)]);
+        let global = GlobalToposort::new(&rules).unwrap();
+        let per_list = PerList::new(&rules);
+        let unrelated = [global.order(&[1, 3]).unwrap()[1], global.order(&[1, 3]).unwrap()[0]];
+        assert!(per_list.is_valid(&unrelated));
+        assert!(!global.is_valid(&unrelated));
+
+        assert!(make_strategy("bogus", &rules).is_err());
+    }
+
+    #[test]
+    fn cycles() {
+        /* The full graph is cyclic, but lists avoiding 3 or 1 can be ordered */
+        let rules = rules(&[(1, 2), (2, 3), (3, 1), (4, 1), (5, 5)]);
+        assert_eq!(vec![4, 1, 2], ordering::order(&[1, 2, 4], &rules).unwrap());
+        assert_eq!(vec![2, 3], ordering::order(&[3, 2], &rules).unwrap());
+
+        let mut found = cycle_of(&[4, 3, 1, 2], &rules).rules();
+        found.sort();
+        assert_eq!(vec![(1, 2), (2, 3), (3, 1)], found);
+        assert_eq!(vec![5], cycle_of(&[4, 5], &rules).pages);
+        assert_eq!("rules 5|5 form a cycle", cycle_of(&[5], &rules).to_string());
+
+        /* The shortest cycle is reported */
+        let rules = self::rules(&[(1, 2), (2, 1), (2, 3), (3, 1)]);
+        assert_eq!(2, cycle_of(&[1, 2, 3], &rules).pages.len());
+
+        let err = fix_lists(vec![vec![3, 2, 1]], &PerList::new(&rules)).unwrap_err().to_string();
+        assert!(err.starts_with("Can't order [3, 2, 1]: rules "), "{}", err);
+
+        assert!(ordering::order(&[1, 1], &rules).is_err());
     }
 }
@@ -0,0 +1,240 @@
+//! Put print lists in an order satisfying the page ordering rules.
+//!
+//! Only the rules between pages of the same list matter, so each list is
+//! ordered by a topological sort of its own subgraph of the rules. The full
+//! rule graph can contain cycles that no single list runs into.
+
+use std::{collections::{HashMap, VecDeque}, error, fmt};
+
+use petgraph::{algo::{tarjan_scc, toposort}, prelude::*, visit::Dfs};
+
+use crate::{Result, Rules};
+
+/// Rules contradicting each other within one print list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    /// Pages around the cycle, each of which must come before the next, and
+    /// the last before the first.
+    pub pages: Vec<u32>,
+}
+
+impl Cycle {
+    /// The rules forming the cycle, as (before, after) pairs.
+    pub fn rules(&self) -> Vec<(u32, u32)> {
+        self.pages.iter()
+            .zip(self.pages.iter().cycle().skip(1))
+            .map(|(&before, &after)| (before, after))
+            .collect()
+    }
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rules: Vec<String> = self.rules().iter().map(|(before, after)| format!("{}|{}", before, after)).collect();
+        write!(f, "rules {} form a cycle", rules.join(", "))
+    }
+}
+
+impl error::Error for Cycle {}
+
+/// Graph of every rule. Pages are added in ascending order, so the output
+/// doesn't depend on hash order.
+pub fn graph(rules: &Rules) -> DiGraphMap<u32, ()> {
+    let mut pairs: Vec<(u32, u32)> = rules.pairs().collect();
+    pairs.sort();
+
+    DiGraphMap::from_edges(&pairs)
+}
+
+/// Graph of the rules between pages of `list`, an edge from each page to the
+/// pages it must come before. Every page of the list is a node.
+pub fn subgraph(list: &[u32], rules: &Rules) -> DiGraphMap<u32, ()> {
+    let mut graph = DiGraphMap::new();
+
+    for &page in list {
+        graph.add_node(page);
+    }
+
+    for &page in list {
+        if let Some(subs) = rules.after(&page) {
+            for &sub in list.iter().filter(|p| subs.contains(p)) {
+                graph.add_edge(page, sub, ());
+            }
+        }
+    }
+
+    graph
+}
+
+/// `list` in an order satisfying the rules between its pages.
+///
+/// Fails with a `Cycle` if those rules contradict each other, and with a
+/// plain message if a page appears more than once.
+pub fn order(list: &[u32], rules: &Rules) -> Result<Vec<u32>> {
+    let graph = subgraph(list, rules);
+
+    if graph.node_count() != list.len() {
+        return Err(format!("Print list repeats a page: {:?}", list).into());
+    }
+
+    toposort(&graph, None).map_err(|cycle| find_cycle(&graph, cycle.node_id()).into())
+}
+
+/// `before[i][j]`: the page at `i` of `list` must come before the page at
+/// `j`, directly or through other pages of the list.
+pub fn closure(list: &[u32], rules: &Rules) -> Vec<Vec<bool>> {
+    let graph = subgraph(list, rules);
+    let index: HashMap<u32, usize> = list.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+    let mut before = vec![vec![false; list.len()]; list.len()];
+
+    for (i, &page) in list.iter().enumerate() {
+        let mut dfs = Dfs::new(&graph, page);
+        while let Some(later) = dfs.next(&graph) {
+            if later != page {
+                before[i][index[&later]] = true;
+            }
+        }
+    }
+
+    before
+}
+
+/// Number of different valid orders of `list`, saturating at `u128::MAX`.
+///
+/// Counts by memoising over the sets of pages that can start a valid order,
+/// so it's quick when the rules nearly fix the order and slow when few pages
+/// are related. Lists are limited to 64 pages.
+pub fn count_orders(list: &[u32], rules: &Rules) -> Result<u128> {
+    order(list, rules)?;
+
+    let n = list.len();
+    if n > 64 {
+        return Err(format!("Can't count orders of more than 64 pages, list has {}", n).into());
+    }
+
+    /* preds[j]: bit i set if the page at i must come before the one at j */
+    let before = closure(list, rules);
+    let preds: Vec<u64> = (0..n)
+        .map(|j| (0..n).filter(|&i| before[i][j]).fold(0, |mask, i| mask | 1 << i))
+        .collect();
+    let full = if n == 64 { u64::MAX } else { (1 << n) - 1 };
+
+    fn count(placed: u64, full: u64, preds: &[u64], memo: &mut HashMap<u64, u128>) -> u128 {
+        if placed == full {
+            return 1;
+        }
+        if let Some(&total) = memo.get(&placed) {
+            return total;
+        }
+
+        let total = (0..preds.len())
+            .filter(|&v| placed & 1 << v == 0 && preds[v] & !placed == 0)
+            .fold(0u128, |total, v| total.saturating_add(count(placed | 1 << v, full, preds, memo)));
+
+        memo.insert(placed, total);
+        total
+    }
+
+    Ok(count(0, full, &preds, &mut HashMap::new()))
+}
+
+/// Up to `cap` valid orders of `list`, in lexicographic order of the
+/// original positions of their pages.
+pub fn orders(list: &[u32], rules: &Rules, cap: usize) -> Result<Vec<Vec<u32>>> {
+    order(list, rules)?;
+
+    let before = closure(list, rules);
+    let mut found = Vec::new();
+
+    fn extend(list: &[u32], before: &[Vec<bool>], current: &mut Vec<usize>, found: &mut Vec<Vec<u32>>, cap: usize) {
+        if found.len() == cap {
+            return;
+        }
+        if current.len() == list.len() {
+            found.push(current.iter().map(|&i| list[i]).collect());
+            return;
+        }
+
+        for v in 0..list.len() {
+            /* Every page that must come before `v` is already placed */
+            let ready = !current.contains(&v) && (0..list.len()).all(|u| !before[u][v] || current.contains(&u));
+            if ready {
+                current.push(v);
+                extend(list, before, current, found, cap);
+                current.pop();
+            }
+        }
+    }
+
+    extend(list, &before, &mut Vec::new(), &mut found, cap);
+    Ok(found)
+}
+
+/// Pages that are in the middle of at least one valid order of `list`.
+///
+/// A page can be at index `k` of some valid order exactly when at most `k`
+/// pages must come before it and at most `n - 1 - k` after it.
+pub fn possible_mids(list: &[u32], rules: &Rules) -> Result<Vec<u32>> {
+    order(list, rules)?;
+
+    if list.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let n = list.len();
+    let mid = n / 2;
+    let before = closure(list, rules);
+
+    Ok((0..n)
+        .filter(|&v| {
+            let preds = (0..n).filter(|&u| before[u][v]).count();
+            let succs = before[v].iter().filter(|&&b| b).count();
+            preds <= mid && succs < n - mid
+        })
+        .map(|v| list[v])
+        .collect())
+}
+
+/// Shortest cycle among the pages strongly connected to `start`, or failing
+/// that to any page, in a graph known to have one.
+fn find_cycle(graph: &DiGraphMap<u32, ()>, start: u32) -> Cycle {
+    let cyclic = |c: &&Vec<u32>| c.len() > 1 || graph.contains_edge(c[0], c[0]);
+    let sccs = tarjan_scc(graph);
+
+    let scc = sccs.iter().filter(cyclic).find(|c| c.contains(&start))
+        .or_else(|| sccs.iter().find(cyclic))
+        .expect("toposort reported a cycle");
+
+    scc.iter()
+        .filter_map(|&from| cycle_through(graph, from, scc))
+        .min_by_key(|cycle| cycle.pages.len())
+        .expect("a strongly connected component has a cycle")
+}
+
+/// Shortest cycle through `from` within its strongly connected component
+/// `scc`, found by a breadth first search back to `from`.
+fn cycle_through(graph: &DiGraphMap<u32, ()>, from: u32, scc: &[u32]) -> Option<Cycle> {
+    let mut parent = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+
+    while let Some(page) = queue.pop_front() {
+        for next in graph.neighbors(page) {
+            if next == from {
+                let mut pages = vec![page];
+                while let Some(&prev) = parent.get(pages.last().unwrap()) {
+                    pages.push(prev);
+                }
+                pages.reverse();
+
+                return Some(Cycle { pages });
+            }
+
+            if scc.contains(&next) && !parent.contains_key(&next) {
+                parent.insert(next, page);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    None
+}
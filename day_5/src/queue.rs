@@ -0,0 +1,94 @@
+//! Print lists checked against rules that change while they're in use.
+//!
+//! A rule `X|Y` only matters to lists containing both X and Y, so when a
+//! rule is added or removed only those lists are checked again.
+
+use std::collections::HashMap;
+
+use crate::{validate_print_list, Rules, Violation};
+
+/// Outcome of changing a rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revalidation {
+    /// How many lists were checked again.
+    pub checked: usize,
+    /// Lists that went from valid to invalid or back.
+    pub changed: Vec<usize>,
+}
+
+pub struct PrintQueue {
+    rules: Rules,
+    lists: Vec<Vec<u32>>,
+    /// Rules each list breaks, kept up to date as the rules change.
+    violations: Vec<Vec<Violation>>,
+    /// K: page#, V: indices of the lists containing it.
+    lists_with: HashMap<u32, Vec<usize>>,
+}
+
+impl PrintQueue {
+    pub fn new(rules: Rules, lists: Vec<Vec<u32>>) -> Self {
+        let violations = lists.iter().map(|list| validate_print_list(list, &rules)).collect();
+
+        let mut lists_with: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (i, list) in lists.iter().enumerate() {
+            for &page in list {
+                /* Once per list, even if it repeats the page */
+                let with = lists_with.entry(page).or_default();
+                if with.last() != Some(&i) {
+                    with.push(i);
+                }
+            }
+        }
+
+        PrintQueue { rules, lists, violations, lists_with }
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn lists(&self) -> &[Vec<u32>] {
+        &self.lists
+    }
+
+    /// Rules list `i` breaks, empty if it's valid.
+    pub fn violations(&self, i: usize) -> &[Violation] {
+        &self.violations[i]
+    }
+
+    pub fn is_valid(&self, i: usize) -> bool {
+        self.violations[i].is_empty()
+    }
+
+    /// Add the rule `before|after` and check the lists it affects. None if
+    /// it was already a rule.
+    pub fn add_rule(&mut self, before: u32, after: u32) -> Option<Revalidation> {
+        self.rules.add(before, after).then(|| self.revalidate(before, after))
+    }
+
+    /// Remove the rule `before|after` and check the lists it affected. None
+    /// if there was no such rule.
+    pub fn remove_rule(&mut self, before: u32, after: u32) -> Option<Revalidation> {
+        self.rules.remove(before, after).then(|| self.revalidate(before, after))
+    }
+
+    /// Check again every list containing both `a` and `b`.
+    fn revalidate(&mut self, a: u32, b: u32) -> Revalidation {
+        let (Some(with_a), Some(with_b)) = (self.lists_with.get(&a), self.lists_with.get(&b)) else {
+            return Revalidation { checked: 0, changed: Vec::new() };
+        };
+
+        let affected: Vec<usize> = with_a.iter().copied().filter(|i| with_b.binary_search(i).is_ok()).collect();
+        let mut changed = Vec::new();
+
+        for &i in &affected {
+            let was_valid = self.is_valid(i);
+            self.violations[i] = validate_print_list(&self.lists[i], &self.rules);
+            if self.is_valid(i) != was_valid {
+                changed.push(i);
+            }
+        }
+
+        Revalidation { checked: affected.len(), changed }
+    }
+}
@@ -0,0 +1,173 @@
+//! Fix print lists by moving as few pages as possible.
+//!
+//! A move takes one page out of the list and puts it back somewhere else.
+//! Pages that aren't moved keep their relative order, so the fewest moves
+//! means keeping the most pages whose relative order the rules already agree
+//! with. Two positions clash when the later page must come before the earlier
+//! one, directly or through other pages of the list. Clashing is a partial
+//! order on positions, and the pages to keep are a largest antichain of it,
+//! found with Dilworth's theorem and a bipartite matching.
+
+use std::{cmp::Reverse, collections::{BinaryHeap, HashMap, VecDeque}, fmt};
+
+use crate::{ordering, Result, Rules};
+
+/// Take `page` out of the list at index `from` and insert it so it ends up at
+/// index `to`. Indices are into the list as it is when the move is made.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Move {
+    pub page: u32,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        /* Positions are shown 1-based */
+        write!(f, "move {} from page {} to page {}", self.page, self.from + 1, self.to + 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reordering {
+    /// The fixed list.
+    pub order: Vec<u32>,
+    /// Moves turning the original list into `order`, in the order to make them.
+    pub moves: Vec<Move>,
+}
+
+/// A valid order for `list` reachable with the fewest moves, and the moves.
+///
+/// Fails like `ordering::order` if the rules between the pages of `list`
+/// contradict each other.
+pub fn reorder(list: &[u32], rules: &Rules) -> Result<Reordering> {
+    /* Rejects cycles and repeated pages up front */
+    ordering::order(list, rules)?;
+
+    let keep = pages_to_keep(list, rules);
+    let order = target_order(list, rules, &keep);
+    let moves = plan_moves(list, &order, &keep);
+
+    Ok(Reordering { order, moves })
+}
+
+/// Which positions of `list` keep their page: a largest set of positions
+/// where no two clash.
+fn pages_to_keep(list: &[u32], rules: &Rules) -> Vec<bool> {
+    let n = list.len();
+    let before = ordering::closure(list, rules);
+    let clash = |i: usize, j: usize| i < j && before[j][i];
+
+    /* Maximum matching between clashing pairs, `matched[j]` is the earlier
+     * position matched to the later position `j` */
+    fn augment(i: usize, clash: &dyn Fn(usize, usize) -> bool, seen: &mut [bool], matched: &mut [Option<usize>]) -> bool {
+        for j in 0..matched.len() {
+            if clash(i, j) && !seen[j] {
+                seen[j] = true;
+                if matched[j].is_none_or(|k| augment(k, clash, seen, matched)) {
+                    matched[j] = Some(i);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    let mut matched = vec![None; n];
+    let has_match: Vec<bool> = (0..n).map(|i| augment(i, &clash, &mut vec![false; n], &mut matched)).collect();
+
+    /* König: walk alternating paths from the unmatched earlier positions.
+     * Positions reached as an earlier one but not as a later one form the
+     * largest antichain. */
+    let mut reached_early: Vec<bool> = has_match.iter().map(|m| !m).collect();
+    let mut reached_late = vec![false; n];
+    let mut queue: VecDeque<usize> = (0..n).filter(|&i| reached_early[i]).collect();
+
+    while let Some(i) = queue.pop_front() {
+        for j in 0..n {
+            if clash(i, j) && !reached_late[j] {
+                reached_late[j] = true;
+                if let Some(k) = matched[j].filter(|&k| !reached_early[k]) {
+                    reached_early[k] = true;
+                    queue.push_back(k);
+                }
+            }
+        }
+    }
+
+    (0..n).map(|i| reached_early[i] && !reached_late[i]).collect()
+}
+
+/// Topological order of `list` that also keeps the pages at the `keep`
+/// positions in their original order. Where there's a choice, pages go in
+/// their original order.
+fn target_order(list: &[u32], rules: &Rules, keep: &[bool]) -> Vec<u32> {
+    let n = list.len();
+    let mut edges = vec![Vec::new(); n];
+
+    for (i, &page) in list.iter().enumerate() {
+        if let Some(subs) = rules.after(&page) {
+            edges[i].extend((0..n).filter(|&j| subs.contains(&list[j])));
+        }
+    }
+
+    let kept: Vec<usize> = (0..n).filter(|&i| keep[i]).collect();
+    for pair in kept.windows(2) {
+        edges[pair[0]].push(pair[1]);
+    }
+
+    let mut indegree = vec![0; n];
+    for &j in edges.iter().flatten() {
+        indegree[j] += 1;
+    }
+
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..n).filter(|&i| indegree[i] == 0).map(Reverse).collect();
+    let mut order = Vec::with_capacity(n);
+
+    while let Some(Reverse(i)) = ready.pop() {
+        order.push(list[i]);
+        for &j in &edges[i] {
+            indegree[j] -= 1;
+            if indegree[j] == 0 {
+                ready.push(Reverse(j));
+            }
+        }
+    }
+
+    order
+}
+
+/// Moves turning `list` into `order`, one per page not kept.
+///
+/// Moved pages are placed in the order they appear in `order`, each straight
+/// after the last page already in its final place. Pages in their final place
+/// stay in the order they have in `order`, so once every page has been placed
+/// the list is `order`.
+fn plan_moves(list: &[u32], order: &[u32], keep: &[bool]) -> Vec<Move> {
+    let mut current = list.to_vec();
+    let mut placed: Vec<bool> = keep.to_vec();
+    let position: HashMap<u32, usize> = list.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+    let mut moves = Vec::new();
+
+    for (t, &page) in order.iter().enumerate() {
+        if placed[position[&page]] {
+            continue;
+        }
+
+        let from = current.iter().position(|&p| p == page).unwrap();
+        current.remove(from);
+
+        let to = match order[..t].iter().rev().find(|p| placed[position[*p]]) {
+            Some(prev) => current.iter().position(|p| p == prev).unwrap() + 1,
+            None => 0,
+        };
+        current.insert(to, page);
+        placed[position[&page]] = true;
+
+        if from != to {
+            moves.push(Move { page, from, to });
+        }
+    }
+
+    moves
+}
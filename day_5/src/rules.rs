@@ -0,0 +1,69 @@
+//! Page ordering rules that can be changed while in use.
+
+use std::collections::{HashMap, HashSet};
+
+/// A set of `X|Y` rules, page X must be printed before page Y, looked up in
+/// either direction.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rules {
+    /// K: page#, V: the pages it must come before.
+    later: HashMap<u32, HashSet<u32>>,
+    /// K: page#, V: the pages that must come before it.
+    earlier: HashMap<u32, HashSet<u32>>,
+}
+
+impl Rules {
+    pub fn new() -> Self {
+        Rules::default()
+    }
+
+    /// Require `before` to be printed before `after`. False if that was
+    /// already a rule.
+    pub fn add(&mut self, before: u32, after: u32) -> bool {
+        self.earlier.entry(after).or_default().insert(before);
+        self.later.entry(before).or_default().insert(after)
+    }
+
+    /// Drop the rule that `before` is printed before `after`. False if there
+    /// was no such rule.
+    pub fn remove(&mut self, before: u32, after: u32) -> bool {
+        fn unlink(map: &mut HashMap<u32, HashSet<u32>>, from: u32, to: u32) -> bool {
+            let Some(set) = map.get_mut(&from) else {
+                return false;
+            };
+            let removed = set.remove(&to);
+            if set.is_empty() {
+                map.remove(&from);
+            }
+            removed
+        }
+
+        unlink(&mut self.earlier, after, before);
+        unlink(&mut self.later, before, after)
+    }
+
+    /// Pages `page` must come before, if any.
+    pub fn after(&self, page: &u32) -> Option<&HashSet<u32>> {
+        self.later.get(page)
+    }
+
+    /// Pages that must come before `page`, if any.
+    pub fn before(&self, page: &u32) -> Option<&HashSet<u32>> {
+        self.earlier.get(page)
+    }
+
+    /// Every rule as a (before, after) pair, in no particular order.
+    pub fn pairs(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.later.iter().flat_map(|(&before, subs)| subs.iter().map(move |&after| (before, after)))
+    }
+}
+
+impl FromIterator<(u32, u32)> for Rules {
+    fn from_iter<I: IntoIterator<Item = (u32, u32)>>(pairs: I) -> Self {
+        let mut rules = Rules::new();
+        for (before, after) in pairs {
+            rules.add(before, after);
+        }
+        rules
+    }
+}
@@ -0,0 +1,80 @@
+//! Interchangeable ways of checking and fixing the order of print lists.
+
+use std::collections::HashMap;
+
+use crate::{ordering, validate_print_list, Result, Rules};
+
+pub trait OrderingStrategy {
+    /// Whether `list` is already in a valid order.
+    fn is_valid(&self, list: &[u32]) -> bool;
+
+    /// `list` in a valid order.
+    fn order(&self, list: &[u32]) -> Result<Vec<u32>>;
+}
+
+/// Check each list against the rules between its own pages, and order it by
+/// toposorting just those. Works whenever a list's own rules are consistent,
+/// whatever the rest of the rules look like.
+pub struct PerList<'a> {
+    rules: &'a Rules,
+}
+
+impl<'a> PerList<'a> {
+    pub fn new(rules: &'a Rules) -> Self {
+        PerList { rules }
+    }
+}
+
+impl OrderingStrategy for PerList<'_> {
+    fn is_valid(&self, list: &[u32]) -> bool {
+        validate_print_list(list, self.rules).is_empty()
+    }
+
+    fn order(&self, list: &[u32]) -> Result<Vec<u32>> {
+        ordering::order(list, self.rules)
+    }
+}
+
+/// Toposort the whole rule graph once, then compare pages by where they came
+/// in that sort.
+///
+/// Only possible when the full graph is acyclic, which the puzzle input's
+/// isn't. Even then it rejects valid lists whose unrelated pages happen to be
+/// in a different order to the global sort. Pages without any rules can go
+/// anywhere.
+pub struct GlobalToposort {
+    /// K: page#, V: toposort index
+    pg2prior: HashMap<u32, u32>,
+}
+
+impl GlobalToposort {
+    /// Fails with an `ordering::Cycle` if the rules contradict each other.
+    pub fn new(rules: &Rules) -> Result<Self> {
+        let mut pages: Vec<u32> = rules.pairs().flat_map(|(before, after)| [before, after]).collect();
+        pages.sort();
+        pages.dedup();
+
+        let sorted = ordering::order(&pages, rules)?;
+        let pg2prior = sorted.iter().enumerate().map(|(i, n)| (*n, i as u32)).collect();
+
+        Ok(GlobalToposort { pg2prior })
+    }
+}
+
+impl OrderingStrategy for GlobalToposort {
+    fn is_valid(&self, list: &[u32]) -> bool {
+        /* Toposort indices should always increase */
+        list.iter()
+            .filter_map(|pg_num| self.pg2prior.get(pg_num))
+            .collect::<Vec<_>>()
+            .windows(2)
+            .all(|pair| pair[0] < pair[1])
+    }
+
+    fn order(&self, list: &[u32]) -> Result<Vec<u32>> {
+        let mut list = list.to_vec();
+        /* Pages without rules (None) sort first */
+        list.sort_by_key(|pg_num| self.pg2prior.get(pg_num));
+        Ok(list)
+    }
+}
@@ -0,0 +1,135 @@
+//! The guards' route, the cells they visit and the obstructions that would
+//! trap them in a loop, as CSV, JSON or a board drawing.
+//!
+//! CSV has one row per item, tagged by the `kind` column:
+//!
+//! ```text
+//! kind,guard,x,y,direction
+//! step,0,4,6,north
+//! visited,,4,1,
+//! loop,,3,6,
+//! ```
+//!
+//! JSON holds the same as three arrays, `steps` of objects and `visited` and
+//! `loop_obstructions` of `[x, y]` pairs.
+
+use std::{collections::HashSet, fmt::Write};
+
+use crate::{Board, CellType, Direction, PathStep, Result, Route};
+
+/// Export file format, told apart by the file extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn from_path(path: &str) -> Result<Self> {
+        match path.rsplit_once('.').map(|(_, ext)| ext) {
+            Some("csv") => Ok(Format::Csv),
+            Some("json") => Ok(Format::Json),
+            _ => Err(format!("Can't tell the export format of {}, use .csv or .json", path).into()),
+        }
+    }
+}
+
+fn direction_name(dir: Direction) -> &'static str {
+    match dir {
+        Direction::North => "north",
+        Direction::East => "east",
+        Direction::South => "south",
+        Direction::West => "west",
+    }
+}
+
+fn direction_from_name(name: &str) -> Option<Direction> {
+    [Direction::North, Direction::East, Direction::South, Direction::West]
+        .into_iter()
+        .find(|&dir| direction_name(dir) == name)
+}
+
+pub fn to_csv(route: &Route, loops: &[(i32, i32)]) -> String {
+    let mut out = String::from("kind,guard,x,y,direction\n");
+
+    for step in &route.steps {
+        writeln!(out, "step,{},{},{},{}", step.guard, step.x, step.y, direction_name(step.dir)).unwrap();
+    }
+    for (x, y) in route.visited() {
+        writeln!(out, "visited,,{},{},", x, y).unwrap();
+    }
+    for (x, y) in loops {
+        writeln!(out, "loop,,{},{},", x, y).unwrap();
+    }
+
+    out
+}
+
+/// The `step` rows of a CSV written by `to_csv`, to replay a recorded path.
+/// Other rows are skipped.
+pub fn read_steps(csv: &str) -> Result<Vec<PathStep>> {
+    let mut steps = Vec::new();
+
+    for (n, line) in csv.lines().enumerate() {
+        let fields: Vec<&str> = line.trim_end().split(',').collect();
+        if fields[0] != "step" {
+            continue;
+        }
+
+        let bad = || format!("Bad step at line {}: {}", n + 1, line);
+        let [_, guard, x, y, dir] = fields[..] else {
+            return Err(bad().into());
+        };
+        steps.push(PathStep {
+            guard: guard.parse().map_err(|_| bad())?,
+            x: x.parse().map_err(|_| bad())?,
+            y: y.parse().map_err(|_| bad())?,
+            dir: direction_from_name(dir).ok_or_else(bad)?,
+        });
+    }
+
+    Ok(steps)
+}
+
+pub fn to_json(route: &Route, loops: &[(i32, i32)]) -> String {
+    let pairs = |cells: &[(i32, i32)]| {
+        cells.iter().map(|(x, y)| format!("[{},{}]", x, y)).collect::<Vec<_>>().join(",")
+    };
+    let steps: Vec<String> = route.steps.iter()
+        .map(|s| format!(r#"{{"guard":{},"x":{},"y":{},"direction":"{}"}}"#, s.guard, s.x, s.y, direction_name(s.dir)))
+        .collect();
+
+    format!(
+        "{{\"steps\":[{}],\"visited\":[{}],\"loop_obstructions\":[{}]}}\n",
+        steps.join(","),
+        pairs(&route.visited()),
+        pairs(loops),
+    )
+}
+
+/// The board with the guards at their starting places, visited cells as `X`
+/// and the loop obstructions as `O`, one line per row.
+pub fn overlay(board: &Board, visited: &[(i32, i32)], loops: &[(i32, i32)]) -> String {
+    let visited: HashSet<&(i32, i32)> = visited.iter().collect();
+    let loops: HashSet<&(i32, i32)> = loops.iter().collect();
+    let mut out = String::new();
+
+    for (y, row) in board.area.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            let at = (x as i32, y as i32);
+            let guard = board.guards.iter().find(|g| g.initial_location == at);
+
+            let c = match (cell, guard) {
+                (CellType::Obstruction(_), _) => '#',
+                _ if loops.contains(&at) => 'O',
+                (_, Some(g)) => g.initial_direction.marker(),
+                _ if visited.contains(&at) => 'X',
+                _ => '.',
+            };
+            out.push(c);
+        }
+        out.push('\n');
+    }
+
+    out
+}
@@ -0,0 +1,117 @@
+//! Precomputed guard moves, from any cell straight to the next turn.
+//!
+//! A guard walks in a straight line until it's in front of an obstruction or
+//! off the board, so for every cell and direction the table holds where that
+//! stretch ends. Walking a guard is then one lookup per turn, and a loop is a
+//! (position, direction) state coming round again.
+
+use std::collections::HashSet;
+
+use crate::Direction;
+
+/// Marks a stretch that runs off the board.
+const OFF_BOARD: u32 = u32::MAX;
+
+#[derive(Clone)]
+pub struct JumpTable {
+    width: usize,
+    height: usize,
+    blocked: Vec<bool>,
+    /// stops[dir][y * width + x]: index of the cell a guard at x,y heading
+    /// `dir` stops in, facing an obstruction, or `OFF_BOARD`.
+    stops: [Vec<u32>; 4],
+}
+
+impl JumpTable {
+    /// Table for a `width` by `height` board with obstructions where
+    /// `blocked` (row by row) is set.
+    pub fn new(width: usize, height: usize, blocked: Vec<bool>) -> Self {
+        let mut table = JumpTable { width, height, blocked, stops: Default::default() };
+        for stops in &mut table.stops {
+            *stops = vec![OFF_BOARD; width * height];
+        }
+
+        for y in 0..height {
+            table.sweep_row(y);
+        }
+        for x in 0..width {
+            table.sweep_column(x);
+        }
+
+        table
+    }
+
+    /// Add or remove the obstruction at `x`,`y`. Only that cell's row and
+    /// column can change, so only they are worked out again.
+    pub fn set_obstruction(&mut self, x: usize, y: usize, blocked: bool) {
+        self.blocked[y * self.width + x] = blocked;
+        self.sweep_row(y);
+        self.sweep_column(x);
+    }
+
+    /// Where a guard at `x`,`y` heading `dir` stops, facing an obstruction.
+    /// None if it walks off the board.
+    pub fn stop(&self, x: usize, y: usize, dir: Direction) -> Option<(usize, usize)> {
+        match self.stops[dir as usize][y * self.width + x] {
+            OFF_BOARD => None,
+            i => Some((i as usize % self.width, i as usize / self.width)),
+        }
+    }
+
+    /// Walk a guard from `x`,`y` heading `dir` until it leaves the board
+    /// (true) or comes back to a position and direction it's been in before
+    /// (false).
+    pub fn walk(&self, x: usize, y: usize, dir: Direction) -> bool {
+        let mut visited = HashSet::new();
+        let (mut x, mut y, mut dir) = (x, y, dir);
+
+        loop {
+            let Some((sx, sy)) = self.stop(x, y, dir) else {
+                return true;
+            };
+            dir.turn();
+            (x, y) = (sx, sy);
+
+            if !visited.insert((x, y, dir)) {
+                return false;
+            }
+        }
+    }
+
+    /// Work out the East and West stops along row `y`.
+    fn sweep_row(&mut self, y: usize) {
+        let cells: Vec<usize> = (0..self.width).map(|x| y * self.width + x).collect();
+        self.sweep(&cells, Direction::West);
+        self.sweep(&cells.into_iter().rev().collect::<Vec<_>>(), Direction::East);
+    }
+
+    /// Work out the North and South stops along column `x`.
+    fn sweep_column(&mut self, x: usize) {
+        let cells: Vec<usize> = (0..self.height).map(|y| y * self.width + x).collect();
+        self.sweep(&cells, Direction::North);
+        self.sweep(&cells.into_iter().rev().collect::<Vec<_>>(), Direction::South);
+    }
+
+    /// Fill in the stops heading `dir` for `cells`, a line ordered so that
+    /// `dir` points back towards its start.
+    fn sweep(&mut self, cells: &[usize], dir: Direction) {
+        let mut stop = OFF_BOARD;
+        let mut behind_obstruction = false;
+
+        for &i in cells {
+            if self.blocked[i] {
+                behind_obstruction = true;
+                self.stops[dir as usize][i] = OFF_BOARD;
+                continue;
+            }
+
+            /* First free cell past an obstruction, where everything further
+             * along stops until the next one */
+            if behind_obstruction {
+                stop = i as u32;
+                behind_obstruction = false;
+            }
+            self.stops[dir as usize][i] = stop;
+        }
+    }
+}
@@ -1,15 +1,25 @@
-use core::panic;
-use std::{ascii, cell::{BorrowError, Cell}, cmp::*, collections::{HashMap, HashSet}, error, fmt, fs::File, io::{self, BufRead, Write}};
+use std::{collections::HashSet, error, fmt, fs::{self, File}, io::{self, BufRead}, time::Instant};
+
+use jump::JumpTable;
+
+mod export;
+mod jump;
+mod playback;
 
 
 // This lets us bubble up all errors to main() regardless of type
 type Error = Box<dyn error::Error>;
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Most guards a board can have, one per bit of an `Obstruction` flag.
+const MAX_GUARDS: usize = 32;
+
 #[derive(Clone, Copy, PartialEq)]
 enum CellType {
-    Gaurd,
-    Obstruction([bool; 4]),
+    Gaurd(Direction),
+    /// Bit `g` of element `d` is set once guard `g` has hit this obstruction
+    /// heading in direction `d`.
+    Obstruction([u32; 4]),
     Visited,
     Empty,
 }
@@ -17,7 +27,7 @@ enum CellType {
 impl fmt::Display for CellType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
        match self {
-           CellType::Gaurd => write!(f, "^"),
+           CellType::Gaurd(dir) => write!(f, "{}", dir.marker()),
            CellType::Obstruction(_) => write!(f, "#"),
            CellType::Visited => write!(f, "X"),
            CellType::Empty => write!(f, "."),
@@ -25,7 +35,7 @@ impl fmt::Display for CellType {
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 enum Direction {
     North = 0,
     East= 1,
@@ -34,6 +44,26 @@ enum Direction {
 }
 
 impl Direction {
+    /// Direction a guard drawn as `c` is facing, if `c` is a guard.
+    fn from_marker(c: char) -> Option<Self> {
+        match c {
+            '^' => Some(Self::North),
+            '>' => Some(Self::East),
+            'v' => Some(Self::South),
+            '<' => Some(Self::West),
+            _ => None,
+        }
+    }
+
+    fn marker(&self) -> char {
+        match self {
+            Self::North => '^',
+            Self::East => '>',
+            Self::South => 'v',
+            Self::West => '<',
+        }
+    }
+
     fn get_move(&self) -> (i32, i32) {
         match self {
             Self::North => (0,-1),
@@ -53,44 +83,145 @@ impl Direction {
     }
 }
 
+#[derive(Clone, Copy)]
+struct Guard {
+    location: Option<(i32, i32)>,   // None == walked out of `area`
+    dir: Direction,
+    initial_location: (i32, i32),
+    initial_direction: Direction,
+}
+
+/// Outcome of one step of the walk.
+enum Step {
+    /// Some guards are still on the board. Holds the cells entered for the
+    /// first time this step.
+    Walking(Vec<(i32, i32)>),
+    /// Every guard has left the board.
+    Left,
+    /// A guard is going round in a loop.
+    Looping,
+}
+
+/// A cell entered on the walk, and the guards just before it first was.
+struct FirstVisit {
+    cell: (i32, i32),
+    guards: Vec<Guard>,
+}
+
+/// Where a guard was, and which way it faced, at one point of the walk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PathStep {
+    guard: usize,
+    x: i32,
+    y: i32,
+    dir: Direction,
+}
+
+/// A walk from the start with no extra obstructions.
+struct Route {
+    /// Every guard on the board at the start and after each step, a guard at
+    /// a time. Turning on the spot is a step too.
+    steps: Vec<PathStep>,
+    /// In the order the cells were first entered.
+    first_visits: Vec<FirstVisit>,
+    /// Whether every guard left the board, rather than looping.
+    left: bool,
+}
+
+impl Route {
+    /// Every cell a guard stood on, row by row.
+    fn visited(&self) -> Vec<(i32, i32)> {
+        let cells: HashSet<(i32, i32)> = self.steps.iter().map(|s| (s.x, s.y)).collect();
+        let mut cells: Vec<(i32, i32)> = cells.into_iter().collect();
+        cells.sort_by_key(|&(x, y)| (y, x));
+        cells
+    }
+}
+
+/// Two guards running into each other.
+#[derive(Debug)]
+struct Collision {
+    guards: (usize, usize),
+    /// Where the first guard came from, when they swapped cells head on.
+    from: Option<(i32, i32)>,
+    at: (i32, i32),
+}
+
+impl fmt::Display for Collision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (a, b) = self.guards;
+        match self.from {
+            Some(from) => write!(f, "Guards {} and {} collided between ({}, {}) and ({}, {})", a, b, from.0, from.1, self.at.0, self.at.1),
+            None => write!(f, "Guards {} and {} collided at ({}, {})", a, b, self.at.0, self.at.1),
+        }
+    }
+}
+
+impl error::Error for Collision {}
+
+/// What a guard does in one step of the walk.
+#[derive(Clone, Copy, PartialEq)]
+enum Plan {
+    Leave,
+    Turn,
+    Move(i32, i32),
+}
+
 #[derive(Clone)]
 struct Board {
     area: Vec<Vec<CellType>>,
-    gaurd_dir: Direction,
-    initial_direction: Direction,
+    guards: Vec<Guard>,
     unique_visits: u32,
-    location: Option<(i32, i32)>,
-    initial_location: (i32, i32),
 }
 
 impl Board {
     fn new() -> Self {
         Board {
             area: Vec::new(),
-            gaurd_dir: Direction::North, // Assume always start facing north
-            initial_direction: Direction::North,
-            unique_visits: 1,
-            location: None,     // None == walked out of `area`
-            initial_location: (0,0),
+            guards: Vec::new(),
+            unique_visits: 0,
         }
     }
 
-    fn reset(&mut self) {
-        self.gaurd_dir = self.initial_direction;
-        self.location = Some(self.initial_location);
-        self.unique_visits = 1;
+    fn add_guard(&mut self, x: i32, y: i32, dir: Direction) {
+        self.guards.push(Guard { location: Some((x, y)), dir, initial_location: (x, y), initial_direction: dir });
+        self.unique_visits += 1;
+    }
 
-        /* Reset Gaurd */
-        let (x, y) = self.initial_location;
-        let start_cell = self.get_cell(x, y).unwrap();
-        *start_cell = CellType::Gaurd;
+    fn reset(&mut self) {
+        let guards = self.guards.iter()
+            .map(|g| Guard { location: Some(g.initial_location), dir: g.initial_direction, ..*g })
+            .collect();
+        self.reset_to(guards);
+        self.unique_visits = self.guards.len() as u32;
+    }
 
-        /* Reset Obstacles */
+    /// Clear the board and carry on from `guards`, as if they had got there
+    /// with no obstructions hit and no cells visited yet.
+    fn reset_to(&mut self, guards: Vec<Guard>) {
+        /* Reset visits and obstacles, then put the guards back */
         for row in &mut self.area {
-            for cell in row.iter_mut().filter(|c| match c {CellType::Obstruction(_) => true, _ => false}) {
-                *cell = CellType::Obstruction([false; 4]);
+            for cell in row.iter_mut() {
+                *cell = match cell {
+                    CellType::Obstruction(_) => CellType::Obstruction([0; 4]),
+                    _ => CellType::Empty,
+                };
             }
         }
+
+        for guard in &guards {
+            if let Some((x, y)) = guard.location {
+                self.area[y as usize][x as usize] = CellType::Gaurd(guard.dir);
+            }
+        }
+        self.guards = guards;
+        self.unique_visits = 0;
+    }
+
+    /// Jump table for the board's obstructions.
+    fn jump_table(&self) -> JumpTable {
+        let blocked = self.area.iter().flatten().map(|c| matches!(c, CellType::Obstruction(_))).collect();
+        JumpTable::new(self.area[0].len(), self.area.len(), blocked)
     }
 
     fn get_cell(&mut self, x: i32, y: i32) -> Option<&mut CellType> {
@@ -107,64 +238,168 @@ impl Board {
         *curr_cell = CellType::Visited;
     }
 
-    fn walk_guard(&mut self, print_board: bool) -> bool {
-        while let Some(current_location) = self.location {
-            let (x, y) = current_location;
-            let (dx, dy) = self.gaurd_dir.get_move();
-
-            let nx = x as i32 + dx;
-            let ny = y as i32 + dy;
-
+    /// Walk every guard until they've all left the board (true) or one of
+    /// them is going round in a loop (false).
+    ///
+    /// Guards move in lockstep. Each step, every guard still on the board
+    /// turns if facing an obstruction, leaves if facing the edge, and
+    /// otherwise moves forward. It's an error for two guards to move into the
+    /// same cell, to swap cells, or for a guard to move into a cell where
+    /// another is turning. Following straight behind another guard is fine.
+    ///
+    /// A guard that hits an obstruction from the same direction twice will
+    /// keep looping, at least until another guard runs into it, so the walk
+    /// stops there.
+    fn walk_guard(&mut self, print_board: bool) -> std::result::Result<bool, Collision> {
+        loop {
             if print_board {
                 self.print_board();
             }
 
-            let curr_dir = self.gaurd_dir;
+            match self.step()? {
+                Step::Walking(_) => {}
+                Step::Left => return Ok(true),
+                Step::Looping => return Ok(false),
+            }
+        }
+    }
 
-            /* handle next step */
-            match self.get_cell(nx, ny) {
-                None => {
+    /// Move every guard once, see `walk_guard`.
+    fn step(&mut self) -> std::result::Result<Step, Collision> {
+        /* Decide every guard's step before any of them takes it */
+        let mut plans = Vec::with_capacity(self.guards.len());
+        for guard in &self.guards {
+            let Some((x, y)) = guard.location else {
+                plans.push(Plan::Leave);
+                continue;
+            };
+            let (dx, dy) = guard.dir.get_move();
+            let (nx, ny) = (x + dx, y + dy);
+
+            plans.push(match self.area.get(ny as usize).and_then(|row| row.get(nx as usize)) {
+                _ if nx < 0 || ny < 0 => Plan::Leave,
+                None => Plan::Leave,
+                Some(CellType::Obstruction(_)) => Plan::Turn,
+                Some(_) => Plan::Move(nx, ny),
+            });
+        }
+
+        self.check_collisions(&plans)?;
+
+        /* Everyone leaves their cell before anyone moves in, so a guard
+         * can follow straight behind another */
+        let mut moved = false;
+        for (g, plan) in plans.iter().enumerate() {
+            let Some((x, y)) = self.guards[g].location else {
+                continue;
+            };
+            let dir = self.guards[g].dir;
+
+            match *plan {
+                Plan::Leave => {
                     self.visit_cell(x, y);
-                    break;
-                },
-                Some(new_cell) => {
-                    match new_cell {
-                        /* Move to empty cell */
-                        CellType::Empty => {
-                            *new_cell = CellType::Gaurd;
-                            self.visit_cell(x, y);
-                            self.location = Some((nx, ny));
-                            self.unique_visits += 1;
-                            }
-                        CellType::Visited =>{
-                            // *new_cell = CellType::Gaurd;    // Do this to help visualize loop search
-                            self.visit_cell(x, y);
-                            self.location = Some((nx, ny));
-                        }
-                        CellType::Obstruction(dirs) => {
-                            /* If we've visited this before with the same Direction
-                            *   we're in a cycle. */
-                            if dirs[curr_dir as usize] {
-                                return false;
-                            }
-                            else {
-                                dirs[curr_dir as usize] = true;
-                            }
-                            self.gaurd_dir.turn();
-                        }
-                        CellType::Gaurd => {
-                            panic!("Gaurd encountered another guard!?");
+                    self.guards[g].location = None;
+                }
+                Plan::Turn => {
+                    /* If this guard has hit this obstruction before with
+                     *  the same Direction it's in a cycle. */
+                    let (dx, dy) = dir.get_move();
+                    if let Some(CellType::Obstruction(dirs)) = self.get_cell(x + dx, y + dy) {
+                        if dirs[dir as usize] & 1 << g != 0 {
+                            return Ok(Step::Looping);
                         }
+                        dirs[dir as usize] |= 1 << g;
+                    }
+                    self.guards[g].dir.turn();
+                    self.area[y as usize][x as usize] = CellType::Gaurd(self.guards[g].dir);
+                }
+                Plan::Move(..) => self.visit_cell(x, y),
+            }
+            moved = true;
+        }
+
+        let mut first_visits = Vec::new();
+        for (g, plan) in plans.iter().enumerate() {
+            if let Plan::Move(nx, ny) = *plan {
+                let dir = self.guards[g].dir;
+                let new_cell = self.get_cell(nx, ny).expect("Cell should exist");
+                let first_visit = *new_cell == CellType::Empty;
+                *new_cell = CellType::Gaurd(dir);
+
+                if first_visit {
+                    self.unique_visits += 1;
+                    first_visits.push((nx, ny));
+                }
+                self.guards[g].location = Some((nx, ny));
+            }
+        }
+
+        if !moved {
+            return Ok(Step::Left);
+        }
+        Ok(Step::Walking(first_visits))
+    }
+
+    /// Walk the board from the start with no extra obstructions, recording
+    /// each cell the guards enter along with the guards as they were just
+    /// before the first of them entered it.
+    fn route(&mut self) -> Result<Route> {
+        self.reset();
+        let mut steps = Vec::new();
+        let mut first_visits = Vec::new();
+
+        loop {
+            let before = self.guards.clone();
+            for (guard, g) in before.iter().enumerate() {
+                if let Some((x, y)) = g.location {
+                    steps.push(PathStep { guard, x, y, dir: g.dir });
+                }
+            }
+
+            match self.step()? {
+                Step::Walking(cells) => {
+                    first_visits.extend(cells.into_iter().map(|cell| FirstVisit { cell, guards: before.clone() }));
+                }
+                Step::Left => return Ok(Route { steps, first_visits, left: true }),
+                Step::Looping => return Ok(Route { steps, first_visits, left: false }),
+            }
+        }
+    }
+
+    /// Fail if carrying out `plans` would make two guards collide.
+    fn check_collisions(&self, plans: &[Plan]) -> std::result::Result<(), Collision> {
+        if self.guards.len() < 2 {
+            return Ok(());
+        }
+
+        /* Where each guard on the board will be after this step */
+        let mut after: Vec<(usize, (i32, i32))> = Vec::new();
+        for (g, (plan, guard)) in plans.iter().zip(&self.guards).enumerate() {
+            match (plan, guard.location) {
+                (Plan::Move(nx, ny), Some(from)) => {
+                    /* Head on, two guards swapping cells */
+                    let swap = plans.iter().zip(&self.guards).position(|(p, other)| {
+                        *p == Plan::Move(from.0, from.1) && other.location == Some((*nx, *ny))
+                    });
+                    if let Some(other) = swap {
+                        return Err(Collision { guards: (g, other), from: Some(from), at: (*nx, *ny) });
                     }
+                    after.push((g, (*nx, *ny)));
                 }
+                (Plan::Turn, Some(at)) => after.push((g, at)),
+                _ => {}
             }
-        // Step once and see what happens
-        // self.print_board();
-        // break;
         }
 
+        after.sort_by_key(|(_, at)| *at);
+        for pair in after.windows(2) {
+            let ((a, at), (b, _)) = (pair[0], pair[1]);
+            if at == pair[1].1 {
+                return Err(Collision { guards: (a, b), from: None, at });
+            }
+        }
 
-        true
+        Ok(())
     }
 
     fn print_board(&self) {
@@ -175,13 +410,12 @@ impl Board {
                 print!("{}", cell);
             }
             print!("|");
-            println!("");
+            println!();
         }
         println!("{:-<width$}",  "-", width = &self.area[0].len()+2);
     }
 }
 
-
 // const INPUT: &str = "data/day6_1_example.txt";
 const INPUT: &str = "data/day6_1.txt";
 
@@ -189,56 +423,193 @@ fn main() -> Result<()> {
     let cwd = std::env::current_dir()?;
     // println!("The current directory is {}", cwd.display());
 
+    /* `--bench` times `find_loops` against the full scan it replaced.
+     * `--export` writes the route and loop obstructions as .csv or .json,
+     * `--overlay` draws them on the board.
+     * `--animate` plays the walk back, or `--replay` a path exported as
+     * .csv, at `--fps` frames a second through a `--viewport` of WxH cells. */
+    let mut bench = false;
+    let mut export_to = None;
+    let mut show_overlay = false;
+    let mut animate = false;
+    let mut replay = None;
+    let mut fps = 20.0;
+    let mut view_size = (80, 30);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bench" => bench = true,
+            "--export" => {
+                let path = args.next().ok_or("--export needs a file name")?;
+                export_to = Some((export::Format::from_path(&path)?, path));
+            }
+            "--overlay" => show_overlay = true,
+            "--animate" => animate = true,
+            "--replay" => replay = Some(args.next().ok_or("--replay needs a file name")?),
+            "--fps" => {
+                fps = args.next().and_then(|n| n.parse().ok()).filter(|&n: &f64| n > 0.0)
+                    .ok_or("--fps needs a number of frames a second")?;
+            }
+            "--viewport" => {
+                view_size = args.next().as_deref().and_then(parse_size)
+                    .ok_or("--viewport needs a size as WxH")?;
+            }
+            _ => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
     /* Open Input file */
     let in_file = File::open(cwd.join(INPUT))?;
 
     let mut board = parse_input(&in_file)?;
 
+    if bench {
+        let start = Instant::now();
+        let routed = find_loops(&mut board.clone())?;
+        let routed_time = start.elapsed();
+
+        let start = Instant::now();
+        let full = find_loops_full(&mut board.clone())?;
+        let full_time = start.elapsed();
+
+        println!("Route only: {} loops in {:?}", routed.len(), routed_time);
+        println!("Full scan:  {} loops in {:?}", full.len(), full_time);
+        println!("Speedup: {:.1}x", full_time.as_secs_f64() / routed_time.as_secs_f64());
+        return Ok(());
+    }
+
+    if animate || replay.is_some() {
+        let steps = match replay {
+            Some(path) => export::read_steps(&fs::read_to_string(path)?)?,
+            None => board.clone().route()?.steps,
+        };
+        let view = playback::Viewport::new(&board, view_size.0, view_size.1);
+        return playback::play(&board, &steps, fps, view);
+    }
+
     board.print_board();
 
-    // board.walk_guard();
-    // println!("Visited {}", &board.unique_visits);
+    let mut walk = board.clone();
+    if walk.walk_guard(false)? {
+        println!("Visited {}", &walk.unique_visits);
+    } else {
+        println!("Visited {} before looping", &walk.unique_visits);
+    }
 
-    let loop_cnt = find_loops(&mut board);
-    println!("Loop count: {}", loop_cnt);
+    let route = board.clone().route()?;
+    let loops = find_loops(&mut board)?;
+    println!("Loop count: {}", loops.len());
+
+    if show_overlay {
+        print!("{}", export::overlay(&board, &route.visited(), &loops));
+    }
+
+    if let Some((format, path)) = export_to {
+        let text = match format {
+            export::Format::Csv => export::to_csv(&route, &loops),
+            export::Format::Json => export::to_json(&route, &loops),
+        };
+        fs::write(&path, text)?;
+        println!("Exported to {}", path);
+    }
 
     Ok(())
 }
 
-fn find_loops(board: &mut Board) -> u32 {
-    let mut loop_count = 0;
+/// Every cell where one extra obstruction leaves a guard going round in a
+/// loop. Fails if the guards collide without an extra obstruction, but an
+/// obstruction that makes them collide just doesn't count.
+///
+/// An obstruction can only change anything on a cell the guards walk
+/// through, and only from the moment one of them first tries to enter it.
+/// So only cells on the unobstructed route are tried, each starting from the
+/// guards as they were just before that moment rather than from the start.
+/// These come first, in the order the guards reach them. Only if the
+/// unobstructed route already loops does every empty cell off the route
+/// count too, added after them row by row.
+///
+/// A lone guard is walked with a `JumpTable`, one lookup per turn. Several
+/// guards are walked a step at a time, as they can only collide in lockstep.
+fn find_loops(board: &mut Board) -> Result<Vec<(i32, i32)>> {
+    let route = board.route()?;
+    let mut loops = Vec::new();
+
+    if board.guards.len() == 1 {
+        let mut table = board.jump_table();
+
+        for visit in &route.first_visits {
+            let (x, y) = (visit.cell.0 as usize, visit.cell.1 as usize);
+            let Some((gx, gy)) = visit.guards[0].location else {
+                continue;
+            };
 
-    print!("Finding loops:");
+            table.set_obstruction(x, y, true);
+            if !table.walk(gx as usize, gy as usize, visit.guards[0].dir) {
+                loops.push(visit.cell);
+            }
+            table.set_obstruction(x, y, false);
+        }
+    }
 
-    for y in 0..board.area.len() {
-        println!("");
-        let _ = std::io::stdout().flush();
+    else {
+        for visit in &route.first_visits {
+            let (x, y) = visit.cell;
+            board.reset_to(visit.guards.clone());
 
-        for x in 0..board.area[y].len() {
-            print!(".");
+            /* Place new Obstruction and test for loop */
+            board.area[y as usize][x as usize] = CellType::Obstruction([0; 4]);
+            let walk = board.walk_guard(false);
+            board.area[y as usize][x as usize] = CellType::Empty;
 
-            // let mut scratch = board.clone(); // Clone becuase we trash the board area while walking it.
+            /* A collision only this obstruction causes isn't a loop */
+            if matches!(walk, Ok(false)) {
+                loops.push(visit.cell);
+            }
+        }
+    }
+
+    if !route.left {
+        let visited: HashSet<(i32, i32)> = route.visited().into_iter().collect();
+        for (y, row) in board.area.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                let cell_at = (x as i32, y as i32);
+                if !matches!(cell, CellType::Obstruction(_)) && !visited.contains(&cell_at) {
+                    loops.push(cell_at);
+                }
+            }
+        }
+    }
+
+    Ok(loops)
+}
+
+/// `find_loops` the slow way, walking the whole board from the start with an
+/// obstruction on every empty cell in turn. Kept to check and benchmark
+/// `find_loops` against.
+fn find_loops_full(board: &mut Board) -> Result<Vec<(i32, i32)>> {
+    let mut loops = Vec::new();
+
+    /* Only a collision without an extra obstruction is an error */
+    board.reset();
+    board.walk_guard(false)?;
+
+    for y in 0..board.area.len() {
+        for x in 0..board.area[y].len() {
             board.reset();
 
             let cell = &mut board.area[y][x];
             let orig_cell_val = *cell;
 
-            if matches!( *cell, CellType::Obstruction(..) ) || CellType::Gaurd == *cell {
+            if matches!( *cell, CellType::Obstruction(..) | CellType::Gaurd(_) ) {
                 /* Skip */
                 continue;
             } else {
                 /* Place new Obstruction and test for loop */
-                *cell = CellType::Obstruction([false; 4]);
-
-                // println!("({}, {})", x, y);
-                // board.print_board();
-
-                // let print_board =  5 == x && 6 == y;
-                let print_board =  false;
+                *cell = CellType::Obstruction([0; 4]);
 
                 /* Walk board looking for a loop */
-                if !board.walk_guard(print_board) {
-                    loop_count += 1;
+                if matches!(board.walk_guard(false), Ok(false)) {
+                    loops.push((x as i32, y as i32));
                 }
             }
 
@@ -247,44 +618,309 @@ fn find_loops(board: &mut Board) -> u32 {
         }
     }
 
-    println!("");
+    Ok(loops)
+}
+
+/// A `WxH` size, both at least 1.
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let (width, height) = size.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
 
-    loop_count
+    (width > 0 && height > 0).then_some((width, height))
 }
 
 fn parse_input(file: &File) -> Result<Board> {
     let reader = io::BufReader::new(file);
+    let lines = reader.lines().collect::<io::Result<Vec<String>>>()?;
+
+    parse_lines(&lines)
+}
 
+fn parse_lines<S: AsRef<str>>(lines: &[S]) -> Result<Board> {
     let mut board = Board::new();
 
-    for (y, line) in reader.lines().enumerate() {
-        let line = line?;
+    for (y, line) in lines.iter().enumerate() {
+        let line = line.as_ref();
         if line.is_empty() {
-            panic!("Unexpected empty line!");
+            return Err(format!("Unexpected empty line {}", y + 1).into());
         }
 
         let mut new_row = Vec::new();
 
         for (x, c) in line.chars().enumerate() {
             match c {
-                '^' => {
-                    new_row.push(CellType::Gaurd);
-                    board.location = Some((x as i32, y as i32));
-                    board.initial_location = board.location.unwrap();
-                },
                 '#' => {
-                    new_row.push(CellType::Obstruction([false; 4]));
+                    new_row.push(CellType::Obstruction([0; 4]));
                 },
                 '.' => {
                     new_row.push(CellType::Empty);
                 }
-                _ => panic!("Unexpected input"),
+                _ => {
+                    let dir = Direction::from_marker(c)
+                        .ok_or_else(|| format!("Unexpected {:?} at line {}, column {}", c, y + 1, x + 1))?;
+                    new_row.push(CellType::Gaurd(dir));
+                    board.add_guard(x as i32, y as i32, dir);
+                }
             }
         }
 
+        if board.area.first().is_some_and(|first| first.len() != new_row.len()) {
+            return Err(format!("Line {} is {} cells wide, expected {}", y + 1, new_row.len(), board.area[0].len()).into());
+        }
         board.area.push(new_row);
     }
 
+    if board.guards.is_empty() {
+        return Err("No guard on the board".into());
+    }
+    if board.guards.len() > MAX_GUARDS {
+        return Err(format!("{} guards on the board, at most {} are supported", board.guards.len(), MAX_GUARDS).into());
+    }
+
     Ok(board)
+}
+
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+    use super::*;
+
+    const EXAMPLE: [&str; 10] = [
+        "....#.....",
+        ".........#",
+        "..........",
+        "..#.......",
+        ".......#..",
+        "..........",
+        ".#..^.....",
+        "........#.",
+        "#.........",
+        "......#...",
+    ];
+
+    fn walk(lines: &[&str]) -> Result<(bool, u32)> {
+        let mut board = parse_lines(lines)?;
+        let left = board.walk_guard(false)?;
+        Ok((left, board.unique_visits))
+    }
+
+    #[test]
+    fn example() {
+        let mut board = parse_lines(&EXAMPLE).unwrap();
+        assert_eq!((true, 41), walk(&EXAMPLE).unwrap());
+        assert_eq!(vec![(3, 6), (6, 7), (3, 8), (1, 8), (7, 7), (7, 9)], find_loops(&mut board).unwrap());
+    }
+
+    #[test]
+    fn route_only_loop_search() {
+        let mut rng = StdRng::seed_from_u64(2024);
+
+        let mut compared = 0;
+        for _ in 0..300 {
+            let (width, height) = (rng.gen_range(3..9), rng.gen_range(3..9));
+            let mut rows: Vec<Vec<char>> = (0..height)
+                .map(|_| (0..width).map(|_| if rng.gen_bool(0.2) { '#' } else { '.' }).collect())
+                .collect();
+
+            /* One or two guards facing any way */
+            for _ in 0..rng.gen_range(1..=2) {
+                let (x, y) = (rng.gen_range(0..width), rng.gen_range(0..height));
+                rows[y][x] = *['^', '>', 'v', '<'].choose(&mut rng).unwrap();
+            }
+
+            let lines: Vec<String> = rows.iter().map(|r| r.iter().collect()).collect();
+            let board = parse_lines(&lines).unwrap();
+
+            /* Both fail only if the guards collide on their way */
+            match (find_loops_full(&mut board.clone()), find_loops(&mut board.clone())) {
+                (Ok(full), Ok(mut routed)) => {
+                    routed.sort_by_key(|&(x, y)| (y, x));
+                    assert_eq!(full, routed, "{:#?}", lines);
+                    compared += 1;
+                }
+                (Err(full), Err(routed)) => assert_eq!(full.to_string(), routed.to_string(), "{:#?}", lines),
+                (full, routed) => panic!("{:?} vs {:?} for {:#?}", full, routed, lines),
+            }
+        }
+        assert!(compared > 250, "only {} boards searched", compared);
+
+        /* Already looping without an extra obstruction */
+        let looping = parse_lines(&[".#..", "...#", "#^..", "..#."]).unwrap();
+        let mut loops = find_loops(&mut looping.clone()).unwrap();
+        loops.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(find_loops_full(&mut looping.clone()).unwrap(), loops);
+    }
+
+    #[test]
+    fn jump_table() {
+        let board = parse_lines(&EXAMPLE).unwrap();
+        let mut table = board.jump_table();
+
+        assert_eq!(Some((4, 1)), table.stop(4, 6, Direction::North));
+        assert_eq!(Some((8, 1)), table.stop(4, 1, Direction::East));
+        assert_eq!(None, table.stop(0, 0, Direction::West));
+        assert_eq!(None, table.stop(9, 9, Direction::South));
+        assert!(table.walk(4, 6, Direction::North));
+
+        /* The example's loop from putting an obstruction beside the start */
+        table.set_obstruction(3, 6, true);
+        assert_eq!(Some((4, 6)), table.stop(7, 6, Direction::West));
+        assert_eq!(Some((4, 6)), table.stop(9, 6, Direction::West));
+        assert!(!table.walk(4, 6, Direction::North));
+
+        table.set_obstruction(3, 6, false);
+        assert_eq!(Some((2, 6)), table.stop(9, 6, Direction::West));
+        assert!(table.walk(4, 6, Direction::North));
+
+        /* Matches a table built from scratch */
+        table.set_obstruction(5, 5, true);
+        let mut rows: Vec<String> = EXAMPLE.iter().map(|r| r.to_string()).collect();
+        rows[5].replace_range(5..6, "#");
+        let fresh = parse_lines(&rows).unwrap().jump_table();
+        for y in 0..10 {
+            for x in 0..10 {
+                for dir in [Direction::North, Direction::East, Direction::South, Direction::West] {
+                    assert_eq!(fresh.stop(x, y, dir), table.stop(x, y, dir), "({}, {}) {:?}", x, y, dir);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn start_directions() {
+        assert_eq!((true, 3), walk(&[">.."]).unwrap());
+        assert_eq!((true, 2), walk(&[".<."]).unwrap());
+        assert_eq!((true, 3), walk(&["v", ".", "."]).unwrap());
+
+        /* Turn right at the obstruction, then walk off the board */
+        assert_eq!((true, 2), walk(&["#.", "^."]).unwrap());
+        assert_eq!((true, 2), walk(&[".v", ".#"]).unwrap());
 
+        let board = parse_lines(&["<.", ".v"]).unwrap();
+        assert_eq!(vec![Direction::West, Direction::South], board.guards.iter().map(|g| g.dir).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn several_guards() {
+        /* Following straight behind another guard is fine */
+        assert_eq!((true, 4), walk(&[">>.."]).unwrap());
+        assert_eq!((true, 6), walk(&["v.", "..", ".^"]).unwrap());
+
+        /* Each guard is walled into its own loop */
+        let looping = [
+            ".#....#...",
+            "....#....#",
+            "#^...#^...",
+            "...#....#.",
+        ];
+        assert!(!walk(&looping).unwrap().0);
+
+        /* Into the same cell, head on, and into a turning guard */
+        let collide = |lines: &[&str]| walk(lines).err().unwrap().to_string();
+        assert_eq!("Guards 0 and 1 collided at (1, 0)", collide(&[">.<"]));
+        assert_eq!("Guards 0 and 1 collided between (0, 0) and (1, 0)", collide(&["><"]));
+        assert_eq!("Guards 0 and 1 collided at (1, 1)", collide(&[".#", ".^", ".^"]));
+
+        /* An obstruction at (2, 0) would make these two swap cells, which
+         * rules that cell out rather than failing the search */
+        let swapping = [".>..", "....", ".^.."];
+        assert_eq!((true, 5), walk(&swapping).unwrap());
+        let loops = find_loops(&mut parse_lines(&swapping).unwrap()).unwrap();
+        assert!(!loops.contains(&(2, 0)));
+        assert_eq!(find_loops_full(&mut parse_lines(&swapping).unwrap()).unwrap(), loops);
+    }
+
+    #[test]
+    fn export() {
+        let mut board = parse_lines(&["#.", "^."]).unwrap();
+        let route = board.route().unwrap();
+        assert_eq!(vec![(0, 1), (1, 1)], route.visited());
+        assert_eq!(
+            "kind,guard,x,y,direction\n\
+             step,0,0,1,north\nstep,0,0,1,east\nstep,0,1,1,east\n\
+             visited,,0,1,\nvisited,,1,1,\n\
+             loop,,1,0,\n",
+            export::to_csv(&route, &[(1, 0)]),
+        );
+        assert_eq!(
+            concat!(
+                r#"{"steps":[{"guard":0,"x":0,"y":1,"direction":"north"},{"guard":0,"x":0,"y":1,"direction":"east"},"#,
+                r#"{"guard":0,"x":1,"y":1,"direction":"east"}],"visited":[[0,1],[1,1]],"loop_obstructions":[]}"#,
+                "\n",
+            ),
+            export::to_json(&route, &[]),
+        );
+        assert_eq!(export::Format::Json, export::Format::from_path("out/path.json").unwrap());
+        assert!(export::Format::from_path("path.txt").is_err());
+
+        let mut board = parse_lines(&EXAMPLE).unwrap();
+        let route = board.route().unwrap();
+        let loops = find_loops(&mut board).unwrap();
+        assert_eq!(41, route.visited().len());
+        let drawn = export::overlay(&board, &route.visited(), &loops);
+        let expected = [
+            "....#.....",
+            "....XXXXX#",
+            "....X...X.",
+            "..#.X...X.",
+            "..XXXXX#X.",
+            "..X.X.X.X.",
+            ".#XO^XXXX.",
+            ".XXXXXOO#.",
+            "#OXOXXXX..",
+            "......#O..",
+        ];
+        assert_eq!(expected.map(|row| format!("{}\n", row)).concat(), drawn);
+    }
+
+    #[test]
+    fn playback() {
+        let mut board = parse_lines(&EXAMPLE).unwrap();
+        let route = board.route().unwrap();
+
+        /* A recorded path replays as it was exported */
+        let steps = export::read_steps(&export::to_csv(&route, &[])).unwrap();
+        assert_eq!(route.steps, steps);
+        assert!(export::read_steps("step,0,1,2,up").is_err());
+
+        /* One frame per step, all guards of a step together */
+        assert_eq!(steps.len(), playback::frames(&steps).len());
+        let two = parse_lines(&["v.", "..", ".^"]).unwrap().route().unwrap();
+        let frames = playback::frames(&two.steps);
+        assert_eq!(vec![2, 2, 2], frames.iter().map(|f| f.len()).collect::<Vec<_>>());
+
+        /* The view follows the guard but stays on the board */
+        let mut view = playback::Viewport::new(&board, 4, 3);
+        view.follow(&board, 4, 6);
+        assert_eq!((2, 5), (view.x, view.y));
+        view.follow(&board, 0, 0);
+        assert_eq!((0, 0), (view.x, view.y));
+        view.follow(&board, 9, 9);
+        assert_eq!((6, 7), (view.x, view.y));
+        assert_eq!(10, playback::Viewport::new(&board, 80, 30).width);
+
+        let frame = playback::frames(&steps)[3];
+        let visited = steps[..=3].iter().map(|s| (s.x, s.y)).collect();
+        let mut view = playback::Viewport::new(&board, 4, 3);
+        view.follow(&board, frame[0].x, frame[0].y);
+        assert_eq!("------\n|....|\n|#.^.|\n|..X.|\n------\n", playback::render(&board, &visited, frame, &view));
+
+        assert_eq!(Some((40, 12)), parse_size("40x12"));
+        assert_eq!(None, parse_size("0x12"));
+        assert_eq!(None, parse_size("40"));
+    }
+
+    #[test]
+    fn bad_input() {
+        let error = |lines: &[String]| parse_lines(lines).err().unwrap().to_string();
+        let lines = |rows: &[&str]| rows.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+
+        assert_eq!("Unexpected 'x' at line 1, column 3", error(&lines(&["..x"])));
+        assert_eq!("Line 2 is 2 cells wide, expected 3", error(&lines(&["..^", ".."])));
+        assert_eq!("Unexpected empty line 2", error(&lines(&["..^", ""])));
+        assert_eq!("No guard on the board", error(&lines(&["..."])));
+        assert!(parse_lines(&[">".repeat(MAX_GUARDS + 1)]).is_err());
+    }
 }
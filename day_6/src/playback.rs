@@ -0,0 +1,208 @@
+//! Animated playback of a guard walk in the terminal.
+//!
+//! Each frame is one step of the walk, redrawn in place with ANSI escapes.
+//! Large boards are shown through a viewport that follows the first guard.
+//! Keys are read without waiting for Enter by switching the terminal out of
+//! canonical mode with `stty`, so this needs a Unix terminal to be
+//! interactive; from a pipe it just plays.
+
+use std::{
+    collections::HashSet,
+    io::{self, Read, Write},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use crate::{Board, CellType, PathStep, Result};
+
+const CONTROLS: &str = "space: pause  n: step  +/-: speed  q or Ctrl-C: quit";
+
+/// What Ctrl-C sends with signals turned off.
+const CTRL_C: u8 = 0x03;
+
+/// The part of the board on screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Viewport {
+    /// A `width` by `height` view of `board`, shrunk to fit it.
+    pub fn new(board: &Board, width: usize, height: usize) -> Self {
+        let (board_width, board_height) = (board.area[0].len(), board.area.len());
+        Viewport { x: 0, y: 0, width: width.min(board_width), height: height.min(board_height) }
+    }
+
+    /// Move the view so `(x, y)` is in the middle, without going past the
+    /// edges of `board`.
+    pub fn follow(&mut self, board: &Board, x: i32, y: i32) {
+        let (board_width, board_height) = (board.area[0].len(), board.area.len());
+        self.x = (x.max(0) as usize).saturating_sub(self.width / 2).min(board_width - self.width);
+        self.y = (y.max(0) as usize).saturating_sub(self.height / 2).min(board_height - self.height);
+    }
+}
+
+/// Split a recorded path into frames, one per step of the walk.
+///
+/// Each step of the walk records its guards in ascending order, so a frame
+/// ends where the guard number stops going up.
+pub fn frames(steps: &[PathStep]) -> Vec<&[PathStep]> {
+    let mut frames = Vec::new();
+    let mut start = 0;
+
+    for i in 1..=steps.len() {
+        if i == steps.len() || steps[i].guard <= steps[i - 1].guard {
+            frames.push(&steps[start..i]);
+            start = i;
+        }
+    }
+
+    frames
+}
+
+/// The cells of `board` in `view`, framed like `Board::print_board`, with
+/// cells in `visited` as `X` and the guards of `frame` drawn facing their
+/// way. Lines end in `\n`.
+pub fn render(board: &Board, visited: &HashSet<(i32, i32)>, frame: &[PathStep], view: &Viewport) -> String {
+    let mut out = format!("{:-<width$}\n", "-", width = view.width + 2);
+
+    for y in view.y..view.y + view.height {
+        out.push('|');
+        for x in view.x..view.x + view.width {
+            let at = (x as i32, y as i32);
+            let c = match frame.iter().find(|s| (s.x, s.y) == at) {
+                Some(step) => step.dir.marker(),
+                None if matches!(board.area[y][x], CellType::Obstruction(_)) => '#',
+                None if visited.contains(&at) => 'X',
+                None => '.',
+            };
+            out.push(c);
+        }
+        out.push_str("|\n");
+    }
+
+    out.push_str(&format!("{:-<width$}\n", "-", width = view.width + 2));
+    out
+}
+
+/// Terminal settings changed for the playback, put back when dropped.
+///
+/// Ctrl-C is read as a key rather than sent as a signal, as being killed
+/// would skip putting them back.
+struct RawTerminal {
+    raw: bool,
+}
+
+impl RawTerminal {
+    fn enter() -> Self {
+        let raw = stty(&["-icanon", "-echo", "-isig", "min", "1"]);
+        /* Clear the screen and hide the cursor */
+        print!("\x1b[2J\x1b[?25l");
+        RawTerminal { raw }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        if self.raw {
+            stty(&["icanon", "echo", "isig"]);
+        }
+        print!("\x1b[?25h");
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Run `stty` on our terminal, whether it worked.
+fn stty(args: &[&str]) -> bool {
+    Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Keys pressed, read on their own thread so frames keep coming while
+/// waiting. The channel closes at the end of input.
+fn keys() -> Receiver<u8> {
+    let (send, recv) = mpsc::channel();
+
+    thread::spawn(move || {
+        for key in io::stdin().lock().bytes() {
+            let Ok(key) = key else { break };
+            if send.send(key).is_err() {
+                break;
+            }
+        }
+    });
+
+    recv
+}
+
+/// Play `steps` over `board` at `fps` frames a second in a `view` sized
+/// viewport, until the last frame or `q`.
+pub fn play(board: &Board, steps: &[PathStep], fps: f64, mut view: Viewport) -> Result<()> {
+    let (width, height) = (board.area[0].len() as i32, board.area.len() as i32);
+    if let Some(step) = steps.iter().find(|s| !(0..width).contains(&s.x) || !(0..height).contains(&s.y)) {
+        return Err(format!("Step at ({}, {}) is off the board", step.x, step.y).into());
+    }
+
+    let frames = frames(steps);
+    let _terminal = RawTerminal::enter();
+    let keys = keys();
+    let mut keys_open = true;
+
+    let mut fps = fps;
+    let mut paused = false;
+    let mut visited = HashSet::new();
+    let mut stdout = io::stdout();
+
+    let mut i = 0;
+    while i < frames.len() {
+        let frame = frames[i];
+        visited.extend(frame.iter().map(|s| (s.x, s.y)));
+        view.follow(board, frame[0].x, frame[0].y);
+
+        /* Home the cursor and draw over the last frame */
+        let status = format!("Step {}/{}  {:.0} fps{}", i + 1, frames.len(), fps, if paused { "  paused" } else { "" });
+        write!(stdout, "\x1b[H{}{}\x1b[K\n{}\x1b[K\n", render(board, &visited, frame, &view), status, CONTROLS)?;
+        stdout.flush()?;
+
+        /* Wait out the frame, or until a key when paused */
+        let key = if !keys_open {
+            thread::sleep(Duration::from_secs_f64(1.0 / fps));
+            None
+        } else if paused {
+            keys.recv().ok()
+        } else {
+            keys.recv_timeout(Duration::from_secs_f64(1.0 / fps)).ok()
+        };
+
+        /* Input closed, nothing can pause or unpause it any more */
+        if key.is_none() && keys_open && matches!(keys.try_recv(), Err(TryRecvError::Disconnected)) {
+            keys_open = false;
+            paused = false;
+        }
+
+        match key {
+            Some(b'q' | CTRL_C) => break,
+            Some(b' ') => paused = !paused,
+            Some(b'n') => {
+                paused = true;
+                i = (i + 1).min(frames.len() - 1);
+            }
+            Some(b'+') => fps = (fps * 2.0).min(1000.0),
+            Some(b'-') => fps = (fps / 2.0).max(0.5),
+            Some(_) => {}
+            None if paused => {}
+            None => i += 1,
+        }
+    }
+
+    Ok(())
+}